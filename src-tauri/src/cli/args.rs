@@ -1,10 +1,14 @@
 use anyhow::{anyhow, Result};
-use clap::{AppSettings, ArgEnum, CommandFactory, Parser};
+use clap::{AppSettings, ArgEnum, Args, CommandFactory, Parser, Subcommand};
 use std::str::FromStr;
 use serde_variant::to_variant_name;
 
 use crate::log;
+use crate::runtime;
 use crate::steps::compiler::{CompilerSetup, ENABLED_SETUPS};
+use crate::steps::options::{
+  BuildMode, BuildVariant, Debugger, DiagnosticsColor, IntelliSense, RunLocation, WarningLevel,
+};
 use crate::tasks;
 
 #[derive(Parser)]
@@ -26,11 +30,11 @@ pub struct CliArgs {
   pub verbose: clap_verbosity_flag::Verbosity,
 
   /// 日志路径
-  #[clap(short = 'L', long)]
+  #[clap(short = 'L', long, global = true)]
   pub log_path: Option<String>,
 
   /// 关闭命令行交互操作，总是假设选择“是”
-  #[clap(short = 'y', long)]
+  #[clap(short = 'y', long, global = true)]
   pub assume_yes: bool,
 
   /// 使用图形界面。当不提供任何命令行参数时，将优先运行 GUI
@@ -41,6 +45,45 @@ pub struct CliArgs {
   #[clap(long, conflicts_with = "gui")]
   cli: bool,
 
+  #[clap(flatten)]
+  pub config: ConfigArgs,
+
+  /// 不发送统计数据
+  #[clap(short = 'S', long, global = true)]
+  pub no_stats: bool,
+
+  /// 跳过扩展管理步骤。仅当扩展管理无法正确运行时使用此选项
+  #[clap(long, global = true)]
+  pub skip_ext_manage: bool,
+
+  #[clap(subcommand)]
+  pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// 不修改任何文件，检查已有配置与扩展是否与给定参数生成的结果一致。存在差异时以非零值退出
+  Check(ConfigArgs),
+  /// 收集源文件并调用编译器。由生成的构建任务调用
+  Build(runtime::build::BuildArgs),
+  /// 运行程序，从输入文件读取标准输入并与答案文件比较。由生成的运行任务调用
+  Io(runtime::io::IoArgs),
+  /// 编译源文件，逐个运行测试数据并报告评测结果
+  Judge(runtime::judge::JudgeArgs),
+  /// 在新的终端窗口中运行程序，结束后显示返回值与用时并等待按键。由生成的运行任务调用
+  Run(runtime::run::RunArgs),
+}
+
+impl Command {
+  /// 是否为由生成的任务调用的运行时子命令
+  pub fn is_runtime(&self) -> bool {
+    !matches!(self, Command::Check(_))
+  }
+}
+
+/// 正常配置与 check 子命令共用的参数
+#[derive(Args)]
+pub struct ConfigArgs {
   /// 指定 VS Code 安装路径。若不提供，则尝试自动检测
   #[clap(long)]
   pub vscode: Option<String>,
@@ -61,20 +104,158 @@ pub struct CliArgs {
   #[clap(short = 'l', long, arg_enum, default_value = "cpp")]
   pub language: Language,
 
-  /// 指定语言标准。若不提供，则工具根据编译器版本选取
-  #[clap(short = 'D', long, possible_values = ["c++98", "c++11", "c++14", "c++17", "c++20", "c++23", "c89", "c99", "c11", "c17"])]
+  /// 指定语言标准，以 gnu 开头的为 GNU 方言。若不提供，则工具根据编译器版本选取。编译器不支持时改用其默认的标准
+  #[clap(short = 'D', long, possible_values = [
+    "c++98", "c++03", "c++11", "c++14", "c++17", "c++20", "c++23", "c++26", "c++2c",
+    "gnu++98", "gnu++03", "gnu++11", "gnu++14", "gnu++17", "gnu++20", "gnu++23", "gnu++26",
+    "c89", "c99", "c11", "c17", "c23", "c2x",
+    "gnu89", "gnu99", "gnu11", "gnu17", "gnu23",
+  ])]
   pub standard: Option<String>,
 
   /// 指定编译选项
   #[clap(short = 'a', long)]
   pub args: Vec<String>,
 
+  /// 指定警告级别：关闭、默认、-Wall -Wextra（MSVC 为 /W4）或在此基础上视警告为错误
+  #[clap(long, possible_values = ["none", "default", "extra", "strict"], default_value = "default")]
+  pub warnings: WarningLevel,
+
+  /// 严格遵循语言标准（-pedantic）
+  #[clap(long)]
+  pub pedantic: bool,
+
+  /// 使用 GNU 方言（如 -std=gnu++20）。MSVC 不可用
+  #[clap(long)]
+  pub gnu: bool,
+
+  /// 以 GBK 编码输出字符串字面量，使程序在简体中文 Windows 的控制台中正确显示。仅 Windows 可用
+  #[clap(long)]
+  pub acp_output: bool,
+
+  /// 指定编译器诊断信息是否带颜色。MSVC 不可用
+  #[clap(long, possible_values = ["auto", "always", "never"], default_value = "auto")]
+  pub diagnostics_color: DiagnosticsColor,
+
+  /// 指定头文件搜索路径，同时加入 IntelliSense 配置，可多次指定
+  #[clap(short = 'I', long = "include-dir")]
+  pub include_dirs: Vec<String>,
+
+  /// 指定库搜索路径，可多次指定
+  #[clap(long = "lib-dir")]
+  pub lib_dirs: Vec<String>,
+
+  /// 指定链接的库，如 m 对应 -lm（MSVC 为 m.lib），可多次指定
+  #[clap(long = "lib")]
+  pub libs: Vec<String>,
+
+  /// 指定预处理宏定义，形如 NAME 或 NAME=VALUE，同时加入 IntelliSense 配置，可多次指定
+  #[clap(long = "define")]
+  pub defines: Vec<String>,
+
+  /// 启用 C++20 模块：构建前预编译 std 模块与工作区中的 .cppm、.ixx 模块单元
+  #[clap(long)]
+  pub modules: bool,
+
+  /// 预编译头文件以加快构建，省略头文件时为 bits/stdc++.h。MSVC 不可用
+  #[clap(
+    long,
+    value_name = "头文件",
+    min_values = 0,
+    default_missing_value = "bits/stdc++.h"
+  )]
+  pub pch: Option<String>,
+
+  /// 指定构建方式：仅当前文件、当前文件夹下的全部源文件或工作区内的全部源文件
+  #[clap(short = 'b', long, possible_values = ["single", "folder", "workspace", "cmake", "make"], default_value = "single")]
+  pub build_mode: BuildMode,
+
+  /// 指定运行任务使用调试构建还是发布构建
+  #[clap(long, possible_values = ["debug", "release"], default_value = "debug")]
+  pub run_variant: BuildVariant,
+
+  /// 指定可执行文件的输出文件夹，可使用 VS Code 变量。指定为空字符串时输出到源文件旁
+  #[clap(long, default_value = "${workspaceFolder}/build")]
+  pub output_dir: String,
+
+  /// 生成或合并 .gitignore，排除构建输出
+  #[clap(long)]
+  pub gitignore: bool,
+
+  /// 多文件构建时排除的文件（glob 格式）
+  #[clap(long)]
+  pub exclude: Vec<String>,
+
+  /// 构建时读取源文件开头指令注释（如 // vscch: -lm）中的编译选项
+  #[clap(long)]
+  pub directives: bool,
+
+  /// 构建时根据源文件包含的头文件自动追加链接库等选项，如 math.h 对应 -lm
+  #[clap(long)]
+  pub auto_libs: bool,
+
+  /// 自动追加选项的额外规则，形如 gmp.h=-lgmp，可多次指定
+  #[clap(long = "lib-rule")]
+  pub lib_rules: Vec<String>,
+
+  /// 使用的库，通过 pkg-config 查询其编译与链接选项，可多次指定
+  #[clap(long = "pkg")]
+  pub libraries: Vec<String>,
+
+  /// 指定 IntelliSense 后端：C/C++ 扩展或 clangd
+  #[clap(long, possible_values = ["cpptools", "clangd"], default_value = "cpptools")]
+  pub intellisense: IntelliSense,
+
+  /// 不生成工作区设置（.vscode/settings.json）
+  #[clap(long)]
+  pub no_settings: bool,
+
+  /// 运行和调试时从与源文件同名的 .in 文件读取输入，并将输出与 .ans 文件比较
+  #[clap(long)]
+  pub io_files: bool,
+
+  /// 指定调试器：CodeLLDB、lldb-dap 或 GDB。仅 LLVM 可用
+  #[clap(long, possible_values = ["codelldb", "lldb-dap", "gdb"], default_value = "codelldb")]
+  pub debugger: Debugger,
+
+  /// 调试时不加载 libstdc++ 的美化输出脚本。仅使用 GDB 时可用
+  #[clap(long)]
+  pub no_pretty_printers: bool,
+
+  /// 调试时不跳过标准库头文件。仅使用 GDB 时可用
+  #[clap(long)]
+  pub no_skip_std: bool,
+
+  /// 调试时不设置 print object on 与 print static-members off。仅使用 GDB 时可用
+  #[clap(long)]
+  pub no_print_object: bool,
+
+  /// 通过 gdbserver 远程调试，指定 ssh 的目标主机或 docker 容器名
+  #[clap(long)]
+  pub remote: Option<String>,
+
+  /// 远程调试时 gdbserver 监听的端口
+  #[clap(long, default_value = "2345")]
+  pub remote_port: u16,
+
+  /// 远程调试时程序复制到的文件夹
+  #[clap(long, default_value = "/tmp")]
+  pub remote_dir: String,
+
+  /// 复制程序并启动 gdbserver 的方式：ssh、docker，或含 {program} {name} {host} {port} {dir} 的命令模板
+  #[clap(long, default_value = "ssh")]
+  pub remote_command: String,
+
   /// 指定运行快捷键
   #[clap(short = 'H', long, default_value = "f6")]
   pub run_hotkey: String,
 
-  /// 启用兼容模式
-  #[clap(short = 'C', long)]
+  /// 指定运行和调试时程序所在之处：集成终端、新的终端窗口或调试控制台
+  #[clap(long, possible_values = ["integrated", "external", "internal"], default_value = "external")]
+  pub run_location: RunLocation,
+
+  /// 已弃用，等同于 --run-location integrated
+  #[clap(short = 'C', long, hide = true, conflicts_with = "run-location")]
   pub compat: bool,
 
   /// 卸载多余的 VS Code 扩展
@@ -105,13 +286,9 @@ pub struct CliArgs {
   #[clap(short = 'T', long)]
   pub no_test: bool,
 
-  /// 不发送统计数据
-  #[clap(short = 'S', long)]
-  pub no_stats: bool,
-
-  /// 跳过扩展管理步骤。仅当扩展管理无法正确运行时使用此选项
+  /// 覆盖已有配置前，逐个显示文件差异并确认是否覆盖
   #[clap(long)]
-  pub skip_ext_manage: bool,
+  pub diff: bool,
 }
 
 #[derive(Clone, PartialEq, ArgEnum)]
@@ -133,6 +310,91 @@ impl FromStr for &'static CompilerSetup {
   }
 }
 
+impl FromStr for BuildMode {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "single" => Ok(BuildMode::Single),
+      "folder" => Ok(BuildMode::Folder),
+      "workspace" => Ok(BuildMode::Workspace),
+      "cmake" => Ok(BuildMode::CMake),
+      "make" => Ok(BuildMode::Make),
+      _ => Err(anyhow!("Unknown build mode: {}", s)),
+    }
+  }
+}
+
+impl FromStr for BuildVariant {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "debug" => Ok(BuildVariant::Debug),
+      "release" => Ok(BuildVariant::Release),
+      _ => Err(anyhow!("Unknown build variant: {}", s)),
+    }
+  }
+}
+
+impl FromStr for IntelliSense {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "cpptools" => Ok(IntelliSense::CppTools),
+      "clangd" => Ok(IntelliSense::Clangd),
+      _ => Err(anyhow!("Unknown IntelliSense backend: {}", s)),
+    }
+  }
+}
+
+impl FromStr for Debugger {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "codelldb" => Ok(Debugger::CodeLldb),
+      "lldb-dap" => Ok(Debugger::LldbDap),
+      "gdb" => Ok(Debugger::Gdb),
+      _ => Err(anyhow!("Unknown debugger: {}", s)),
+    }
+  }
+}
+
+impl FromStr for RunLocation {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "integrated" => Ok(RunLocation::Integrated),
+      "external" => Ok(RunLocation::External),
+      "internal" => Ok(RunLocation::Internal),
+      _ => Err(anyhow!("Unknown run location: {}", s)),
+    }
+  }
+}
+
+impl FromStr for WarningLevel {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "none" => Ok(WarningLevel::None),
+      "default" => Ok(WarningLevel::Default),
+      "extra" => Ok(WarningLevel::Extra),
+      "strict" => Ok(WarningLevel::Strict),
+      _ => Err(anyhow!("Unknown warning level: {}", s)),
+    }
+  }
+}
+
+impl FromStr for DiagnosticsColor {
+  type Err = anyhow::Error;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(DiagnosticsColor::Auto),
+      "always" => Ok(DiagnosticsColor::Always),
+      "never" => Ok(DiagnosticsColor::Never),
+      _ => Err(anyhow!("Unknown diagnostics color: {}", s)),
+    }
+  }
+}
+
 fn print_setup_help() {
   println!("\n\x1b[33mSETUPS:\x1b[0m");
   for (i, setup) in ENABLED_SETUPS.iter().enumerate() {
@@ -146,8 +408,17 @@ fn print_setup_help() {
   }
 }
 
+/// 是否以运行时子命令启动。全局选项可以位于子命令之前，故须完整解析命令行
+#[cfg(windows)]
+pub fn is_runtime() -> bool {
+  CliArgs::try_parse().map_or(false, |a| a.command.map_or(false, |c| c.is_runtime()))
+}
+
 pub fn parse() -> Result<CliArgs> {
   let args = CliArgs::try_parse()?;
+  if args.command.as_ref().map_or(false, Command::is_runtime) {
+    return Ok(args);
+  }
   log::setup(args.log_path.as_ref(), args.verbose.log_level_filter())?;
   tasks::statistics::set(!args.no_stats);
   if args.skip_ext_manage {
@@ -21,12 +21,15 @@ use anyhow::Result;
 use crate::cli::args::Language;
 use crate::gui::gui;
 use crate::log;
-use crate::steps::options::Options;
+use crate::runtime;
+use crate::steps::options::{Options, RemoteDebug, RunLocation};
 use crate::tasks;
 #[cfg(windows)]
 use crate::utils::winapi;
 use crate::utils::ToString;
-use args::CliArgs;
+#[cfg(windows)]
+pub use args::is_runtime;
+use args::{CliArgs, Command};
 
 mod args;
 mod prompt;
@@ -72,7 +75,7 @@ macro_rules! out {
 pub fn run() {
   let no_args = std::env::args().len() <= 1;
 
-  let args = match args::parse() {
+  let mut args = match args::parse() {
     Ok(args) => args,
     Err(e) => {
       if no_args {
@@ -93,6 +96,20 @@ pub fn run() {
     }
   };
 
+  let result = match args.command.take() {
+    Some(Command::Build(args)) => Some(runtime::build::run(args)),
+    Some(Command::Io(args)) => Some(runtime::io::run(args)),
+    Some(Command::Judge(args)) => Some(runtime::judge::run(args)),
+    Some(Command::Run(args)) => Some(runtime::run::run(args)),
+    command => {
+      args.command = command;
+      None
+    }
+  };
+  if let Some(result) = result {
+    runtime_handled(result);
+  }
+
   if no_args {
     #[cfg(windows)]
     if !has_webview2_installed() {
@@ -112,7 +129,19 @@ pub fn run() {
   }
 }
 
+/// 运行时子命令不写日志，直接以其返回值退出
+fn runtime_handled(result: Result<i32>) -> ! {
+  match result {
+    Ok(code) => std::process::exit(code),
+    Err(e) => {
+      eprintln!("[\x1b[31mERROR\x1b[0m] {:#}", e);
+      std::process::exit(1)
+    }
+  }
+}
+
 fn cli_handled(args: CliArgs) {
+  let checking = matches!(args.command, Some(Command::Check(_)));
   match cli(args) {
     Ok(_) => (),
     Err(e) => {
@@ -120,7 +149,10 @@ fn cli_handled(args: CliArgs) {
       out!(
         "如果你认为该错误是 bug，请将 {} 文件发送到 guyutongxue@163.com。",
         log::get_log_path().to_string()
-      )
+      );
+      if checking {
+        std::process::exit(2);
+      }
     }
   }
   #[cfg(windows)]
@@ -152,11 +184,16 @@ fn gui_handled() {
   }
 }
 
-fn cli(mut args: CliArgs) -> Result<()> {
+fn cli(args: CliArgs) -> Result<()> {
   if args::early_exit(&args) {
     return Ok(());
   };
 
+  let (mut config, checking) = match args.command {
+    Some(Command::Check(config)) => (config, true),
+    _ => (args.config, false),
+  };
+
   if !cfg!(windows) {
     fn nonsupport_check(name: &'static str, flag: &mut bool) {
       if *flag {
@@ -164,34 +201,41 @@ fn cli(mut args: CliArgs) -> Result<()> {
         *flag = false;
       }
     }
-    nonsupport_check("--ascii-check", &mut args.ascii_check);
-    nonsupport_check("--no-set-env", &mut args.no_set_env);
-    nonsupport_check("--desktop-shortcut", &mut args.desktop_shortcut);
+    nonsupport_check("--acp-output", &mut config.acp_output);
+    nonsupport_check("--ascii-check", &mut config.ascii_check);
+    nonsupport_check("--no-set-env", &mut config.no_set_env);
+    nonsupport_check("--desktop-shortcut", &mut config.desktop_shortcut);
   }
 
   info!("检查 VS Code 安装...");
-  let vscode = prompt::vscode(args.vscode, args.assume_yes)?;
+  let vscode = prompt::vscode(config.vscode, args.assume_yes)?;
   info!("VS Code 安装在 {}，", vscode);
 
-  let setup = prompt::setup(args.setup, args.assume_yes)?;
+  let setup = prompt::setup(config.setup, args.assume_yes)?;
 
   info!("验证 {} 类型编译器...", setup.id);
-  let compiler = prompt::compiler(setup, args.compiler, args.assume_yes)?;
+  let compiler = prompt::compiler(setup, config.compiler, args.assume_yes)?;
   info!("编译器路径为 {}。", compiler.path);
 
   info!("检查工作区路径...");
-  let workspace = prompt::workspace(args.workspace, args.assume_yes)?;
+  let workspace = prompt::workspace(config.workspace, args.assume_yes)?;
   info!("工作区路径为 {}。", workspace);
 
-  let language = if args.language == Language::Cpp {
+  let language = if config.language == Language::Cpp {
     "C++"
   } else {
     "C"
   };
-  let add_to_path = !args.no_set_env;
-  let test = if args.test {
+  let add_to_path = !config.no_set_env;
+  let run_location = if config.compat {
+    warn!("-C/--compat 选项已弃用，请改用 --run-location integrated。");
+    RunLocation::Integrated
+  } else {
+    config.run_location
+  };
+  let test = if config.test {
     Some(true)
-  } else if args.no_test {
+  } else if config.no_test {
     Some(false)
   } else {
     None
@@ -203,23 +247,75 @@ fn cli(mut args: CliArgs) -> Result<()> {
     compiler: compiler,
     options: Options {
       language: language.into(),
-      args: args.args,
-      standard: args.standard,
-      run_hotkey: args.run_hotkey,
-      compatible_mode: args.compat,
-      remove_extensions: args.remove_extensions,
-      ascii_check: args.ascii_check,
+      args: config.args,
+      warning_level: config.warnings,
+      pedantic: config.pedantic,
+      use_gnu: config.gnu,
+      acp_output: config.acp_output,
+      diagnostics_color: config.diagnostics_color,
+      include_dirs: config.include_dirs,
+      lib_dirs: config.lib_dirs,
+      libs: config.libs,
+      defines: config.defines,
+      modules: config.modules,
+      pch: config.pch,
+      build_mode: config.build_mode,
+      run_variant: config.run_variant,
+      output_dir: config.output_dir,
+      gitignore: config.gitignore,
+      exclude: config.exclude,
+      directives: config.directives,
+      auto_libs: config.auto_libs,
+      lib_rules: config.lib_rules,
+      libraries: config.libraries,
+      intellisense: config.intellisense,
+      workspace_settings: !config.no_settings,
+      io_files: config.io_files,
+      debugger: config.debugger,
+      gdb_pretty_printers: !config.no_pretty_printers,
+      gdb_skip_std: !config.no_skip_std,
+      gdb_print_object: !config.no_print_object,
+      remote: config.remote.map(|host| RemoteDebug {
+        host: host,
+        port: config.remote_port,
+        dir: config.remote_dir,
+        command: config.remote_command,
+      }),
+      standard: config.standard,
+      run_hotkey: config.run_hotkey,
+      run_location: run_location,
+      remove_extensions: config.remove_extensions,
+      ascii_check: config.ascii_check,
       add_to_path: add_to_path,
-      desktop_shortcut: args.desktop_shortcut,
+      desktop_shortcut: config.desktop_shortcut,
       test: test,
-      open_vscode: args.open_vscode,
+      open_vscode: config.open_vscode,
       collect_data: !args.no_stats,
     },
+    skipped_files: vec![],
   };
   debug!("task_init_args: {:?}", task_init_args);
 
+  let mut task_args = tasks::init(task_init_args);
+  if checking {
+    info!("检查已有配置...");
+    let report = tasks::check::run(&task_args)?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    info!("检测到 {} 处与期望配置不一致。", report.drifts.len());
+    std::process::exit(if report.passed { 0 } else { 1 });
+  }
+  if config.diff {
+    info!("比较已有配置与将生成的配置...");
+    for diff in tasks::diff::generate(&task_args)? {
+      if !prompt::diff(&diff, args.assume_yes)? {
+        info!("将保留 {}。", diff.path);
+        task_args.skipped_files.push(diff.path.into());
+      }
+    }
+  }
+
   info!("正在初始化任务列表...");
-  let task_list = tasks::list(task_init_args);
+  let task_list = tasks::list(task_args);
   debug!(
     "任务列表：{:?}",
     task_list.iter().map(|t| t.0).collect::<Vec<_>>()
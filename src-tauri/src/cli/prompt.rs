@@ -6,6 +6,8 @@ use crate::out;
 use crate::steps::compiler::{Compiler, CompilerSetup, ENABLED_SETUPS};
 use crate::steps::vscode;
 use crate::steps::workspace;
+use crate::tasks::diff::FileDiff;
+use crate::utils::ansi_enabled;
 
 pub fn vscode(arg: Option<String>, y: bool) -> Result<String> {
   if let Some(origin) = arg {
@@ -174,3 +176,34 @@ pub fn workspace(arg: Option<String>, y: bool) -> Result<String> {
     Ok(prompt_one(question)?.as_string().unwrap().into())
   }
 }
+
+pub fn diff(diff: &FileDiff, y: bool) -> Result<bool> {
+  out!("{} 将被修改：", diff.path);
+  let ansi = ansi_enabled();
+  for line in diff.diff.lines() {
+    if !ansi {
+      println!("{}", line);
+      continue;
+    }
+    let color = if line.starts_with("+++") || line.starts_with("---") {
+      1
+    } else if line.starts_with('+') {
+      32
+    } else if line.starts_with('-') {
+      31
+    } else if line.starts_with('@') {
+      36
+    } else {
+      0
+    };
+    println!("\x1b[{}m{}\x1b[0m", color, line);
+  }
+  if y {
+    return Ok(true);
+  }
+  let question = Question::confirm("diff")
+    .message(format!("是否覆盖 {}？", diff.path))
+    .default(true)
+    .build();
+  Ok(prompt_one(question)?.as_bool().unwrap())
+}
@@ -25,7 +25,7 @@ use crate::steps::{
   vscode, workspace,
 };
 use crate::tasks;
-use crate::tasks::TaskInitArgs;
+use crate::tasks::{diff::FileDiff, TaskInitArgs};
 
 pub fn gui() -> Result<()> {
   debug!("即将启动 tauri GUI。");
@@ -39,6 +39,7 @@ pub fn gui() -> Result<()> {
       compiler_install,
       workspace_verify,
       options_scan,
+      workspace_diff,
       task_init
     ])
     .run(tauri::generate_context!())?;
@@ -181,6 +182,8 @@ struct EnabledOptions {
   ascii_check_enabled: bool,
   add_to_path_enabled: bool,
   desktop_shortcut_enabled: bool,
+  debugger_enabled: bool,
+  gdb_enabled: bool,
 }
 
 #[tauri::command]
@@ -193,11 +196,33 @@ fn options_scan(setup: &str) -> EnabledOptions {
     ascii_check_enabled: ascii_check_enabled(setup),
     add_to_path_enabled: add_to_path_enabled(setup),
     desktop_shortcut_enabled: desktop_shortcut_enabled(setup),
+    debugger_enabled: debugger_enabled(setup),
+    gdb_enabled: gdb_enabled(setup),
   };
   trace!("options_scan: -> {:?}", result);
   result
 }
 
+#[derive(Serialize, Debug)]
+#[serde(tag = "type")]
+enum DiffResult {
+  Ok { value: Vec<FileDiff> },
+  Err { message: String },
+}
+
+#[tauri::command]
+fn workspace_diff(args: TaskInitArgs) -> DiffResult {
+  trace!("workspace_diff: <- {:?}", args);
+  let result = match tasks::diff::generate(&tasks::init(args)) {
+    Ok(value) => DiffResult::Ok { value },
+    Err(e) => DiffResult::Err {
+      message: e.to_string(),
+    },
+  };
+  trace!("workspace_diff: -> {:?}", result);
+  result
+}
+
 #[derive(Serialize, Clone, Debug)]
 #[serde(tag = "type")]
 enum TaskFinishResult {
@@ -208,7 +233,7 @@ enum TaskFinishResult {
 #[tauri::command]
 fn task_init(args: TaskInitArgs, window: tauri::Window) -> Vec<&'static str> {
   trace!("task_init: <- {:?}", args);
-  let t = tasks::list(args);
+  let t = tasks::list(tasks::init(args));
   let names = t.iter().map(|t| t.0).collect::<Vec<_>>();
   std::thread::spawn(move || {
     for (name, action) in t {
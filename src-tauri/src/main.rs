@@ -20,6 +20,7 @@
 mod cli;
 mod gui;
 mod log;
+mod runtime;
 mod steps;
 mod tasks;
 mod utils;
@@ -27,7 +28,12 @@ mod utils;
 fn main() {
   #[cfg(windows)]
   {
-    utils::winapi::alloc_console();
+    if cli::is_runtime() {
+      // 由 VS Code 任务调用时，输出应显示在任务所在的终端中
+      utils::winapi::attach_console();
+    } else {
+      utils::winapi::alloc_console();
+    }
     utils::winapi::enable_virtual_terminal();
   }
   cli::run();
@@ -0,0 +1,151 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+// 根据源文件包含的头文件，自动追加对应的链接与编译选项。
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use regex::Regex;
+
+#[derive(Args)]
+pub struct AutoLibArgs {
+  /// 根据源文件包含的头文件自动追加链接库等选项
+  #[clap(long)]
+  pub auto_libs: bool,
+
+  /// 额外的头文件规则，形如 gmp.h=-lgmp。与内置规则的头文件相同时覆盖之
+  #[clap(long = "lib-rule")]
+  pub lib_rules: Vec<String>,
+}
+
+/// 内置规则：头文件、GCC/Clang 的选项、MSVC 的选项
+static RULES: &[(&str, &[&str], &[&str])] = &[
+  ("math.h", &["-lm"], &[]),
+  ("pthread.h", &["-pthread"], &[]),
+  ("threads.h", &["-pthread"], &[]),
+  ("thread", &["-pthread"], &[]),
+  ("omp.h", &["-fopenmp"], &["/openmp"]),
+  ("ncurses.h", &["-lncurses"], &[]),
+  ("curses.h", &["-lncurses"], &[]),
+  ("winsock2.h", &["-lws2_32"], &["ws2_32.lib"]),
+];
+
+/// 解析形如 `header=flags` 的规则
+pub fn parse_rule(rule: &str) -> Result<(String, Vec<String>)> {
+  match rule.split_once('=') {
+    Some((header, flags)) if !header.trim().is_empty() => Ok((
+      header.trim().to_string(),
+      flags.split_whitespace().map(|f| f.to_string()).collect(),
+    )),
+    _ => Err(anyhow!("头文件规则 {} 不合法，应形如 gmp.h=-lgmp。", rule)),
+  }
+}
+
+/// 源文件中 #include 的头文件。源文件可能是 GBK 等编码，#include 行只含 ASCII 字符，
+/// 故不要求合法的 UTF-8
+fn includes(re: &Regex, source: &Path) -> Result<Vec<String>> {
+  let content = fs::read(source).map_err(|e| anyhow!("无法读取 {:?}：{}", source, e))?;
+  let content = String::from_utf8_lossy(&content);
+  Ok(
+    content
+      .lines()
+      .filter_map(|l| re.captures(l))
+      .map(|c| c[1].trim().to_string())
+      .collect(),
+  )
+}
+
+/// 源文件所包含的头文件对应的选项，去除重复
+pub fn flags(args: &AutoLibArgs, sources: &[&Path], msvc: bool) -> Result<Vec<String>> {
+  let mut rules = vec![];
+  for rule in &args.lib_rules {
+    rules.push(parse_rule(rule)?);
+  }
+  if args.auto_libs {
+    for (header, gnu, cl) in RULES {
+      if !rules.iter().any(|(h, _)| h == header) {
+        let flags = if msvc { cl } else { gnu };
+        rules.push((
+          header.to_string(),
+          flags.iter().map(|f| f.to_string()).collect(),
+        ));
+      }
+    }
+  }
+  let mut result: Vec<String> = vec![];
+  if rules.is_empty() {
+    return Ok(result);
+  }
+  let re = Regex::new(r#"^\s*#\s*include\s*[<"]([^>"]+)[>"]"#).unwrap();
+  for source in sources {
+    for header in includes(&re, source)? {
+      if let Some((_, flags)) = rules.iter().find(|(h, _)| *h == header) {
+        for flag in flags {
+          if !result.contains(flag) {
+            result.push(flag.clone());
+          }
+        }
+      }
+    }
+  }
+  if !result.is_empty() {
+    println!("根据包含的头文件追加编译选项：{}", result.join(" "));
+  }
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_rule() {
+    assert_eq!(
+      parse_rule(" gmp.h = -lgmp  -lgmpxx ").unwrap(),
+      (
+        "gmp.h".to_string(),
+        vec!["-lgmp".to_string(), "-lgmpxx".to_string()]
+      )
+    );
+    assert_eq!(parse_rule("x.h=").unwrap(), ("x.h".to_string(), vec![]));
+    assert!(parse_rule("gmp.h").is_err());
+    assert!(parse_rule(" =-lgmp").is_err());
+  }
+
+  #[test]
+  fn test_flags() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("a.c");
+    // 注释为 GBK 编码
+    fs::write(
+      &source,
+      b"// \xc4\xe3\xba\xc3\n#include <math.h>\n  # include \"gmp.h\"\n#include <stdio.h>\n",
+    )
+    .unwrap();
+    let args = AutoLibArgs {
+      auto_libs: true,
+      lib_rules: vec!["gmp.h=-lgmp".to_string(), "math.h=-lm -lmvec".to_string()],
+    };
+    assert_eq!(
+      flags(&args, &[source.as_path()], false).unwrap(),
+      vec!["-lm", "-lmvec", "-lgmp"]
+    );
+  }
+}
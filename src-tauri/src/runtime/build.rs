@@ -0,0 +1,162 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use glob::Pattern;
+
+use super::autolib::{self, AutoLibArgs};
+use super::directive;
+use super::modules::{self, ModuleArgs};
+use super::pch::{self, PchArgs};
+
+#[derive(Args)]
+pub struct BuildArgs {
+  /// 源文件扩展名
+  #[clap(long = "ext", requires = "dir")]
+  pub exts: Vec<String>,
+
+  /// 排除的文件，glob 格式，相对于查找源文件的文件夹
+  #[clap(long)]
+  pub exclude: Vec<String>,
+
+  /// 同时查找子文件夹
+  #[clap(long)]
+  pub recursive: bool,
+
+  /// 输出文件夹，不存在时创建
+  #[clap(long)]
+  pub out_dir: Option<PathBuf>,
+
+  /// 读取源文件开头指令注释中的编译选项
+  #[clap(long)]
+  pub directives: bool,
+
+  /// 读取其中指令注释与所包含头文件的源文件。查找到的源文件也会读取
+  #[clap(long)]
+  pub scan: Vec<PathBuf>,
+
+  #[clap(flatten)]
+  pub auto_lib: AutoLibArgs,
+
+  #[clap(flatten)]
+  pub modules: ModuleArgs,
+
+  #[clap(flatten)]
+  pub pch: PchArgs,
+
+  /// 查找源文件的文件夹。若不提供，则不查找源文件，直接调用编译器
+  pub dir: Option<String>,
+
+  /// 编译器及其参数。找到的源文件会插入到编译器之后
+  #[clap(last = true, required = true)]
+  pub command: Vec<String>,
+}
+
+fn collect(
+  dir: &Path,
+  root: &Path,
+  args: &BuildArgs,
+  patterns: &[Pattern],
+  result: &mut Vec<PathBuf>,
+) -> Result<()> {
+  let entries = fs::read_dir(dir).map_err(|e| anyhow!("无法读取文件夹 {:?}：{}", dir, e))?;
+  for entry in entries {
+    let path = entry?.path();
+    let relative = path
+      .strip_prefix(root)?
+      .to_string_lossy()
+      .replace('\\', "/");
+    if patterns.iter().any(|p| p.matches(&relative)) {
+      continue;
+    }
+    if path.is_dir() {
+      // 跳过 .vscode、.git 等隐藏文件夹
+      let hidden = path
+        .file_name()
+        .map_or(false, |n| n.to_string_lossy().starts_with('.'));
+      if args.recursive && !hidden {
+        collect(&path, root, args, patterns, result)?;
+      }
+    } else if path
+      .extension()
+      .map_or(false, |e| args.exts.iter().any(|x| e == x.as_str()))
+    {
+      result.push(path);
+    }
+  }
+  Ok(())
+}
+
+pub fn sources(args: &BuildArgs, dir: &str) -> Result<Vec<PathBuf>> {
+  let root = Path::new(dir);
+  let patterns = args
+    .exclude
+    .iter()
+    .map(|p| Pattern::new(p).map_err(|e| anyhow!("排除规则 {} 不合法：{}", p, e)))
+    .collect::<Result<Vec<_>>>()?;
+  let mut result = vec![];
+  collect(root, root, args, &patterns, &mut result)?;
+  result.sort();
+  Ok(result)
+}
+
+pub fn run(args: BuildArgs) -> Result<i32> {
+  if let Some(out_dir) = &args.out_dir {
+    fs::create_dir_all(out_dir).map_err(|e| anyhow!("无法创建输出文件夹 {:?}：{}", out_dir, e))?;
+  }
+  let mut sources = vec![];
+  if let Some(dir) = &args.dir {
+    if args.exts.is_empty() {
+      return Err(anyhow!("查找源文件时须通过 --ext 指定扩展名。"));
+    }
+    sources = self::sources(&args, dir)?;
+    if sources.is_empty() {
+      return Err(anyhow!("在 {} 中找不到源文件。", dir));
+    }
+    println!("编译 {} 个源文件：", sources.len());
+    for source in &sources {
+      println!("  {}", source.display());
+    }
+  }
+  let (compiler, rest) = args.command.split_first().unwrap();
+  let scanned: Vec<&Path> = args
+    .scan
+    .iter()
+    .chain(&sources)
+    .map(|p| p.as_path())
+    .collect();
+  let is_msvc = directive::is_msvc(compiler);
+  let mut flags = autolib::flags(&args.auto_lib, &scanned, is_msvc)?;
+  if args.directives {
+    flags.extend(directive::read_all(&scanned, is_msvc)?);
+  }
+  let rest = directive::merge(rest, flags);
+  let module_args = modules::prepare(&args.modules, compiler, &rest, &scanned)?;
+  let pch_args = pch::prepare(&args.pch, compiler, &rest, &scanned)?;
+  let status = Command::new(compiler)
+    .args(&pch_args)
+    .args(&module_args)
+    .args(&sources)
+    .args(&rest)
+    .status()?;
+  Ok(status.code().unwrap_or(1))
+}
@@ -0,0 +1,322 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+// 源文件开头的指令注释，如 `// vscch: -lm -O2`，为单个源文件追加编译选项。
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+static PREFIX: &str = "vscch:";
+
+/// 会改变输出位置、另外写入文件、替换编译器组件或加载任意代码的选项。-Wl, 等会将任意选项
+/// 传给链接器、汇编器，-fprofile-generate、-fdump-tree-all 等会在任意位置写入文件，
+/// 须在 -W 与 -f 之前排除
+static DANGEROUS_GNU: &[&str] = &[
+  "-o",
+  "-B",
+  "-specs",
+  "-wrapper",
+  "-fplugin",
+  "-fuse-ld",
+  "-Xclang",
+  "-Xlinker",
+  "-Xassembler",
+  "-Xpreprocessor",
+  "-Wl,",
+  "-Wa,",
+  "-Wp,",
+  "-x",
+  "-E",
+  "-S",
+  "-c",
+  "--sysroot",
+  "-fprofile-",
+  "-fcs-profile-generate",
+  "-ftest-coverage",
+  "-fdump-",
+  "-fsave-",
+  "-foptimization-record",
+  "-fopt-info",
+  "-fstack-usage",
+  "-fcallgraph-info",
+  "-ftime-trace",
+  "-fcrash-diagnostics",
+  "-fmodule-output",
+  "-fmodules-cache-path",
+];
+static ALLOWED_GNU: &[&str] = &[
+  "-l",
+  "-L",
+  "-I",
+  "-D",
+  "-U",
+  "-O",
+  "-g",
+  "-W",
+  "-f",
+  "-m",
+  "-std=",
+  "-pthread",
+  "-static",
+  "-pedantic",
+  "-w",
+  "-rdynamic",
+];
+static DANGEROUS_MSVC: &[&str] = &["/Fe", "/Fo", "/Fd", "/Fa", "/Fp", "/E", "/P", "/c", "/link"];
+static ALLOWED_MSVC: &[&str] = &[
+  "/O",
+  "/D",
+  "/U",
+  "/I",
+  "/W",
+  "/w",
+  "/std:",
+  "/EH",
+  "/utf-8",
+  "/MD",
+  "/MT",
+  "/Z",
+  "/permissive",
+  "/fsanitize",
+];
+
+/// 编译器是否为 MSVC 的 cl.exe
+pub fn is_msvc(compiler: &str) -> bool {
+  Path::new(compiler)
+    .file_stem()
+    .map_or(false, |s| s.eq_ignore_ascii_case("cl"))
+}
+
+/// 按空白拆分，支持以双引号包含空格，以 \" 表示双引号本身
+fn split(text: &str) -> Result<Vec<String>> {
+  let mut result = vec![];
+  let mut current = String::new();
+  let mut in_word = false;
+  let mut quoted = false;
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' if chars.peek() == Some(&'"') => {
+        current.push(chars.next().unwrap());
+        in_word = true;
+      }
+      '"' => {
+        quoted = !quoted;
+        in_word = true;
+      }
+      c if c.is_whitespace() && !quoted => {
+        if in_word {
+          result.push(std::mem::take(&mut current));
+          in_word = false;
+        }
+      }
+      c => {
+        current.push(c);
+        in_word = true;
+      }
+    }
+  }
+  if quoted {
+    return Err(anyhow!("引号不匹配：{}", text));
+  }
+  if in_word {
+    result.push(current);
+  }
+  Ok(result)
+}
+
+fn check(flag: &str, msvc: bool) -> Result<()> {
+  let (dangerous, allowed) = if msvc {
+    (DANGEROUS_MSVC, ALLOWED_MSVC)
+  } else {
+    (DANGEROUS_GNU, ALLOWED_GNU)
+  };
+  // MSVC 的 /E 等单字母选项只按完整的选项名匹配，以免误伤 /EHsc 之类
+  let is_dangerous = dangerous.iter().any(|d| {
+    if msvc && d.len() == 2 {
+      flag == *d
+    } else {
+      flag.starts_with(d)
+    }
+  });
+  if is_dangerous || flag.starts_with('@') {
+    return Err(anyhow!("不允许在指令注释中使用 {}。", flag));
+  }
+  // MSVC 直接接受 .lib 文件作为链接输入
+  let is_lib = msvc && flag.to_ascii_lowercase().ends_with(".lib") && !flag.starts_with('/');
+  if !is_lib && !allowed.iter().any(|a| flag.starts_with(a)) {
+    return Err(anyhow!(
+      "指令注释中有未知的选项 {}。只支持库、头文件路径、宏定义、优化、警告与语言标准等选项。",
+      flag
+    ));
+  }
+  Ok(())
+}
+
+/// 读取源文件开头的指令注释中的编译选项。只检查位于第一行代码之前的注释。
+/// 源文件可能是 GBK 等编码，指令本身只含 ASCII 字符，故不要求文件为合法的 UTF-8
+pub fn read(source: &Path, msvc: bool) -> Result<Vec<String>> {
+  let content = fs::read(source).map_err(|e| anyhow!("无法读取 {:?}：{}", source, e))?;
+  let content = String::from_utf8_lossy(&content);
+  let mut flags = vec![];
+  for line in content.lines() {
+    let line = line.trim_start_matches('\u{feff}').trim();
+    if line.is_empty() {
+      continue;
+    }
+    let comment = if let Some(c) = line.strip_prefix("//") {
+      c
+    } else if let Some(c) = line.strip_prefix("/*") {
+      c.trim_end().trim_end_matches("*/")
+    } else {
+      break;
+    };
+    if let Some(directive) = comment.trim().strip_prefix(PREFIX) {
+      for flag in split(directive)? {
+        check(&flag, msvc).map_err(|e| anyhow!("{}：{}", source.display(), e))?;
+        flags.push(flag);
+      }
+    }
+  }
+  Ok(flags)
+}
+
+/// 读取多个源文件的指令注释，去除重复的选项
+pub fn read_all(sources: &[&Path], msvc: bool) -> Result<Vec<String>> {
+  let mut result: Vec<String> = vec![];
+  for source in sources {
+    let flags = read(source, msvc)?;
+    if !flags.is_empty() {
+      println!("{} 指定了编译选项：{}", source.display(), flags.join(" "));
+    }
+    for flag in flags {
+      if !result.contains(&flag) {
+        result.push(flag);
+      }
+    }
+  }
+  Ok(result)
+}
+
+/// 将指令注释中的选项追加到已有参数之后，跳过已有的选项。MSVC 的 /link 之后均为链接器选项，故插入到它之前
+pub fn merge(rest: &[String], flags: Vec<String>) -> Vec<String> {
+  let mut result = rest.to_vec();
+  let mut position = rest
+    .iter()
+    .position(|a| a.eq_ignore_ascii_case("/link"))
+    .unwrap_or(rest.len());
+  for flag in flags {
+    if !result.contains(&flag) {
+      result.insert(position, flag);
+      position += 1;
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_split() {
+    assert_eq!(split("  -lm   -O2 ").unwrap(), vec!["-lm", "-O2"]);
+    assert_eq!(
+      split(r#"-DNAME=\"Alice\" "-I my dir""#).unwrap(),
+      vec![r#"-DNAME="Alice""#, "-I my dir"]
+    );
+    assert_eq!(split(r#"-DE="""#).unwrap(), vec!["-DE="]);
+    assert!(split(r#"-I"a b"#).is_err());
+  }
+
+  #[test]
+  fn test_check() {
+    for flag in [
+      "-lm",
+      "-O2",
+      "-Wall",
+      "-fsanitize=address",
+      "-std=c++20",
+      "-DX=1",
+    ] {
+      assert!(check(flag, false).is_ok(), "{}", flag);
+    }
+    for flag in [
+      "-o",
+      "-B/tmp",
+      "-fplugin=x.so",
+      "-fuse-ld=/tmp/ld",
+      "-Wl,-rpath,/tmp",
+      "-Wa,-o,x",
+      "-Wp,-MD,x",
+      "-Xlinker",
+      "-Xassembler",
+      "@args",
+      "--unknown",
+      "-fprofile-generate=/tmp/p",
+      "-fprofile-dir=x",
+      "-fprofile-arcs",
+      "-fprofile-instr-generate",
+      "-ftest-coverage",
+      "-fdump-tree-all",
+      "-fsave-optimization-record",
+      "-fopt-info-all=x",
+      "-fstack-usage",
+      "-fcallgraph-info",
+      "-ftime-trace",
+    ] {
+      assert!(check(flag, false).is_err(), "{}", flag);
+    }
+    for flag in ["/O2", "/EHsc", "/std:c++20", "user32.lib"] {
+      assert!(check(flag, true).is_ok(), "{}", flag);
+    }
+    for flag in ["/E", "/Fe:a.exe", "/link", "/Bx"] {
+      assert!(check(flag, true).is_err(), "{}", flag);
+    }
+  }
+
+  #[test]
+  fn test_merge() {
+    let rest = vec!["-O2".to_string(), "-lm".to_string()];
+    assert_eq!(
+      merge(&rest, vec!["-lm".to_string(), "-lgmp".to_string()]),
+      vec!["-O2", "-lm", "-lgmp"]
+    );
+    let rest = vec![
+      "/O2".to_string(),
+      "/link".to_string(),
+      "/LIBPATH:x".to_string(),
+    ];
+    assert_eq!(
+      merge(&rest, vec!["a.lib".to_string(), "/DX".to_string()]),
+      vec!["/O2", "a.lib", "/DX", "/link", "/LIBPATH:x"]
+    );
+  }
+
+  #[test]
+  fn test_read_non_utf8() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("a.cpp");
+    // GBK 编码的 “// 你好”
+    let content =
+      b"\xef\xbb\xbf// \xc4\xe3\xba\xc3\n// vscch: -lm\nint main() {}\n// vscch: -lgmp\n".to_vec();
+    fs::write(&source, content).unwrap();
+    assert_eq!(read(&source, false).unwrap(), vec!["-lm"]);
+  }
+}
@@ -0,0 +1,156 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+
+use crate::utils::ansi_enabled;
+
+#[derive(Args)]
+pub struct IoArgs {
+  /// 输入文件。存在时作为程序的标准输入
+  #[clap(long)]
+  pub input: Option<PathBuf>,
+
+  /// 答案文件。存在时在程序结束后与其标准输出比较
+  #[clap(long)]
+  pub answer: Option<PathBuf>,
+
+  /// 输入文件不存在时创建空文件后退出，不运行程序。用于调试前准备重定向的输入
+  #[clap(long, requires = "input")]
+  pub touch: bool,
+
+  /// 程序及其参数
+  #[clap(last = true, required_unless_present = "touch")]
+  pub command: Vec<String>,
+}
+
+/// 去除每行末尾空白及末尾的空行
+pub fn normalize(text: &str) -> Vec<&str> {
+  let mut lines: Vec<&str> = text.lines().map(|l| l.trim_end()).collect();
+  while lines.last().map_or(false, |l| l.is_empty()) {
+    lines.pop();
+  }
+  lines
+}
+
+/// 返回第一处不同的行号（从 1 开始）及期望、实际的内容；一致时返回 None
+pub fn first_difference(output: &str, answer: &str) -> Option<(usize, String, String)> {
+  let output = normalize(output);
+  let answer = normalize(answer);
+  let len = output.len().max(answer.len());
+  (0..len).find(|&i| output.get(i) != answer.get(i)).map(|i| {
+    (
+      i + 1,
+      answer.get(i).map_or("<EOF>".into(), |l| l.to_string()),
+      output.get(i).map_or("<EOF>".into(), |l| l.to_string()),
+    )
+  })
+}
+
+pub fn run(args: IoArgs) -> Result<i32> {
+  if args.touch {
+    let input = args.input.unwrap();
+    if !input.exists() {
+      File::create(&input).map_err(|e| anyhow!("无法创建 {}：{}", input.display(), e))?;
+      println!("已创建空的输入文件 {}。", input.display());
+    }
+    return Ok(0);
+  }
+  let (program, rest) = args.command.split_first().unwrap();
+  let mut command = Command::new(program);
+  command.args(rest);
+  if let Some(input) = args.input.as_ref().filter(|p| p.exists()) {
+    println!("从 {} 读取输入。", input.display());
+    command.stdin(File::open(input)?);
+  }
+  let answer = match args.answer.as_ref().filter(|p| p.exists()) {
+    Some(answer) => answer,
+    None => {
+      let status = command.status()?;
+      return Ok(status.code().unwrap_or(1));
+    }
+  };
+
+  // 需要比较时截获标准输出，同时原样转发到终端
+  let mut child = command
+    .stdout(Stdio::piped())
+    .spawn()
+    .map_err(|e| anyhow!("无法启动 {}：{}", program, e))?;
+  let mut stdout = child.stdout.take().unwrap();
+  let mut output = vec![];
+  let mut buffer = [0u8; 4096];
+  loop {
+    let n = stdout.read(&mut buffer)?;
+    if n == 0 {
+      break;
+    }
+    std::io::stdout().write_all(&buffer[..n])?;
+    std::io::stdout().flush()?;
+    output.extend_from_slice(&buffer[..n]);
+  }
+  let status = child.wait()?;
+
+  let output = String::from_utf8_lossy(&output);
+  // 答案文件可能是 GBK 等非 UTF-8 编码，与输出同样按字节宽松解码
+  let answer_text = String::from_utf8_lossy(&fs::read(answer)?).to_string();
+  let (green, red, reset) = if ansi_enabled() {
+    ("\x1b[32m", "\x1b[31m", "\x1b[0m")
+  } else {
+    ("", "", "")
+  };
+  println!();
+  match first_difference(&output, &answer_text) {
+    None => println!("{}与 {} 一致。{}", green, answer.display(), reset),
+    Some((line, expected, actual)) => {
+      println!(
+        "{}与 {} 不一致：第 {} 行{}",
+        red,
+        answer.display(),
+        line,
+        reset
+      );
+      println!("  期望：{}", expected);
+      println!("  实际：{}", actual);
+    }
+  }
+  Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_first_difference() {
+    assert_eq!(first_difference("1 2 \n3\n\n", "1 2\n3"), None);
+    assert_eq!(
+      first_difference("1\n2\n", "1\n3\n"),
+      Some((2, "3".to_string(), "2".to_string()))
+    );
+    assert_eq!(
+      first_difference("1\n", "1\n2\n"),
+      Some((2, "2".to_string(), "<EOF>".to_string()))
+    );
+    assert_eq!(first_difference("1\r\n2\r\n", "1\n2"), None);
+  }
+}
@@ -0,0 +1,453 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use unicode_width::UnicodeWidthStr;
+
+use super::autolib::{self, AutoLibArgs};
+use super::directive;
+use super::io::first_difference;
+use crate::tasks::dotvscode::EXT;
+use crate::utils::ansi_enabled;
+
+#[derive(Args)]
+pub struct JudgeArgs {
+  /// 待评测的源文件
+  pub source: PathBuf,
+
+  /// 测试数据文件夹。若不提供，则查找源文件旁的 name.N.in 与 name.N.out，或 tests 文件夹
+  #[clap(long)]
+  pub tests: Option<PathBuf>,
+
+  /// 每个测试点的时间限制（毫秒）
+  #[clap(long, default_value = "1000")]
+  pub time_limit: u64,
+
+  /// 每个测试点的内存限制（MiB）。Windows 上不限制内存，也不判定 MLE
+  #[clap(long, default_value = "256")]
+  pub memory_limit: u64,
+
+  /// 读取源文件开头指令注释中的编译选项
+  #[clap(long)]
+  pub directives: bool,
+
+  #[clap(flatten)]
+  pub auto_lib: AutoLibArgs,
+
+  /// 编译器及其参数。源文件与输出选项会插入到编译器之后
+  #[clap(last = true)]
+  pub command: Vec<String>,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Verdict {
+  AC,
+  WA,
+  TLE,
+  MLE,
+  RE,
+}
+
+impl Verdict {
+  fn text(&self) -> &'static str {
+    match self {
+      Verdict::AC => "AC ",
+      Verdict::WA => "WA ",
+      Verdict::TLE => "TLE",
+      Verdict::MLE => "MLE",
+      Verdict::RE => "RE ",
+    }
+  }
+
+  fn colored(&self) -> &'static str {
+    match self {
+      Verdict::AC => "\x1b[32mAC \x1b[0m",
+      Verdict::WA => "\x1b[31mWA \x1b[0m",
+      Verdict::TLE => "\x1b[33mTLE\x1b[0m",
+      Verdict::MLE => "\x1b[33mMLE\x1b[0m",
+      Verdict::RE => "\x1b[35mRE \x1b[0m",
+    }
+  }
+}
+
+struct Case {
+  name: String,
+  input: PathBuf,
+  answer: PathBuf,
+}
+
+/// 程序运行的结果。memory 以 KiB 计，无法获取时为 None
+struct Usage {
+  success: bool,
+  timed_out: bool,
+  time: Duration,
+  memory: Option<u64>,
+}
+
+/// 答案文件，.out 或 .ans
+fn answer_of(input: &Path) -> Option<PathBuf> {
+  ["out", "ans"]
+    .iter()
+    .map(|ext| input.with_extension(ext))
+    .find(|p| p.exists())
+}
+
+/// 按其中的数字排序，使 2 排在 10 之前
+fn natural_key(name: &str) -> (Vec<u64>, String) {
+  let numbers = name
+    .split(|c: char| !c.is_ascii_digit())
+    .filter(|s| !s.is_empty())
+    .filter_map(|s| s.parse().ok())
+    .collect();
+  (numbers, name.to_string())
+}
+
+fn collect_cases(args: &JudgeArgs) -> Result<Vec<Case>> {
+  let dir = args
+    .source
+    .parent()
+    .filter(|p| !p.as_os_str().is_empty())
+    .unwrap_or_else(|| Path::new("."));
+  let stem = args
+    .source
+    .file_stem()
+    .ok_or(anyhow!("源文件 {:?} 不合法。", args.source))?
+    .to_string_lossy()
+    .to_string();
+
+  // 优先使用 name.N.in；其次是测试数据文件夹中的全部 .in
+  let (folder, prefix) = match &args.tests {
+    Some(tests) => (tests.clone(), None),
+    None => (dir.to_path_buf(), Some(format!("{}.", stem))),
+  };
+  let mut cases = find_cases(&folder, prefix.as_deref())?;
+  if cases.is_empty() && args.tests.is_none() {
+    let tests = dir.join("tests");
+    if tests.is_dir() {
+      cases = find_cases(&tests, None)?;
+    }
+  }
+  cases.sort_by_key(|c| natural_key(&c.name));
+  Ok(cases)
+}
+
+fn find_cases(folder: &Path, prefix: Option<&str>) -> Result<Vec<Case>> {
+  let entries = fs::read_dir(folder).map_err(|e| anyhow!("无法读取文件夹 {:?}：{}", folder, e))?;
+  let mut cases = vec![];
+  for entry in entries {
+    let path = entry?.path();
+    if path.extension().map_or(true, |e| e != "in") {
+      continue;
+    }
+    let name = path.file_stem().unwrap().to_string_lossy().to_string();
+    if let Some(prefix) = prefix {
+      // name.N.in 中的 N 须为数字
+      match name.strip_prefix(prefix) {
+        Some(n) if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) => (),
+        _ => continue,
+      }
+    }
+    if let Some(answer) = answer_of(&path) {
+      cases.push(Case {
+        name: name,
+        input: path,
+        answer: answer,
+      });
+    }
+  }
+  Ok(cases)
+}
+
+fn compile(args: &JudgeArgs, program: &Path) -> Result<()> {
+  let default_command = if args.source.extension().map_or(false, |e| e == "c") {
+    vec!["cc".to_string(), "-O2".to_string()]
+  } else {
+    vec!["c++".to_string(), "-O2".to_string()]
+  };
+  let command = if args.command.is_empty() {
+    &default_command
+  } else {
+    &args.command
+  };
+  let (compiler, rest) = command.split_first().unwrap();
+  let is_msvc = directive::is_msvc(compiler);
+  let sources = [args.source.as_path()];
+  let mut flags = autolib::flags(&args.auto_lib, &sources, is_msvc)?;
+  if args.directives {
+    flags.extend(directive::read_all(&sources, is_msvc)?);
+  }
+  let rest = directive::merge(rest, flags);
+  let output = if is_msvc { "/Fe:" } else { "-o" };
+  println!("编译 {}...", args.source.display());
+  let status = Command::new(compiler)
+    .arg(&args.source)
+    .arg(output)
+    .arg(program)
+    .args(&rest)
+    .status()
+    .map_err(|e| anyhow!("无法启动编译器 {}：{}", compiler, e))?;
+  if !status.success() {
+    return Err(anyhow!("编译失败。"));
+  }
+  Ok(())
+}
+
+#[cfg(unix)]
+fn set_limits(command: &mut Command, args: &JudgeArgs) {
+  use std::os::unix::process::CommandExt;
+  // CPU 时间向上取整到秒并多留一秒，精确的判定以实测时间为准
+  let cpu = args.time_limit / 1000 + 2;
+  // 地址空间比实际占用大得多，这里只防止失控，MLE 以峰值常驻内存判定
+  let memory = args.memory_limit * 1024 * 1024 * 2;
+  unsafe {
+    command.pre_exec(move || {
+      let cpu = libc::rlimit {
+        rlim_cur: cpu as libc::rlim_t,
+        rlim_max: cpu as libc::rlim_t,
+      };
+      let memory = libc::rlimit {
+        rlim_cur: memory as libc::rlim_t,
+        rlim_max: memory as libc::rlim_t,
+      };
+      libc::setrlimit(libc::RLIMIT_CPU, &cpu);
+      libc::setrlimit(libc::RLIMIT_AS, &memory);
+      Ok(())
+    });
+  }
+}
+
+#[cfg(not(unix))]
+fn set_limits(_command: &mut Command, _args: &JudgeArgs) {}
+
+#[cfg(unix)]
+fn wait(mut child: Child, wall_limit: Duration) -> Result<Usage> {
+  let pid = child.id() as libc::pid_t;
+  let start = Instant::now();
+  let mut timed_out = false;
+  loop {
+    let mut status = 0;
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::wait4(pid, &mut status, libc::WNOHANG, &mut rusage) };
+    if ret < 0 {
+      return Err(std::io::Error::last_os_error().into());
+    }
+    if ret == pid {
+      let cpu = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+      let signaled = libc::WIFSIGNALED(status);
+      return Ok(Usage {
+        success: libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0,
+        timed_out: timed_out || (signaled && libc::WTERMSIG(status) == libc::SIGXCPU),
+        time: cpu(rusage.ru_utime) + cpu(rusage.ru_stime),
+        // Linux 下 ru_maxrss 以 KiB 计，macOS 下以字节计
+        memory: Some(if cfg!(target_os = "macos") {
+          rusage.ru_maxrss as u64 / 1024
+        } else {
+          rusage.ru_maxrss as u64
+        }),
+      });
+    }
+    if !timed_out && start.elapsed() > wall_limit {
+      // 例如等待输入而不占用 CPU 的程序
+      timed_out = true;
+      let _ = child.kill();
+    }
+    std::thread::sleep(Duration::from_millis(2));
+  }
+}
+
+#[cfg(not(unix))]
+fn wait(mut child: Child, wall_limit: Duration) -> Result<Usage> {
+  let start = Instant::now();
+  loop {
+    if let Some(status) = child.try_wait()? {
+      return Ok(Usage {
+        success: status.success(),
+        timed_out: false,
+        time: start.elapsed(),
+        memory: None,
+      });
+    }
+    if start.elapsed() > wall_limit {
+      let _ = child.kill();
+      let _ = child.wait();
+      return Ok(Usage {
+        success: false,
+        timed_out: true,
+        time: start.elapsed(),
+        memory: None,
+      });
+    }
+    std::thread::sleep(Duration::from_millis(2));
+  }
+}
+
+fn run_case(
+  args: &JudgeArgs,
+  program: &Path,
+  case: &Case,
+  output: &Path,
+) -> Result<(Verdict, Usage, String)> {
+  let mut command = Command::new(program);
+  command
+    .stdin(File::open(&case.input)?)
+    .stdout(File::create(output)?)
+    .stderr(Stdio::null());
+  set_limits(&mut command, args);
+  let child = command.spawn()?;
+  let time_limit = Duration::from_millis(args.time_limit);
+  let usage = wait(child, time_limit * 2 + Duration::from_secs(1))?;
+
+  let mut detail = String::new();
+  let verdict = if usage.timed_out || usage.time > time_limit {
+    Verdict::TLE
+  } else if usage.memory.map_or(false, |m| m > args.memory_limit * 1024) {
+    Verdict::MLE
+  } else if !usage.success {
+    Verdict::RE
+  } else {
+    let output = String::from_utf8_lossy(&fs::read(output)?).to_string();
+    // 答案文件可能是 GBK 等非 UTF-8 编码，与输出同样按字节宽松解码
+    let answer = String::from_utf8_lossy(&fs::read(&case.answer)?).to_string();
+    match first_difference(&output, &answer) {
+      None => Verdict::AC,
+      Some((line, expected, actual)) => {
+        detail = format!("第 {} 行：期望 {:?}，实际 {:?}", line, expected, actual);
+        Verdict::WA
+      }
+    }
+  };
+  Ok((verdict, usage, detail))
+}
+
+pub fn run(args: JudgeArgs) -> Result<i32> {
+  let cases = collect_cases(&args)?;
+  if cases.is_empty() {
+    return Err(anyhow!(
+      "找不到测试数据。请在源文件旁放置 name.1.in 与 name.1.out，或将成对的 .in 与 .out 放入 tests 文件夹。"
+    ));
+  }
+  // 每次评测使用独立的临时文件夹，同时评测多个程序时互不干扰；结束后自动删除
+  let temp = tempfile::Builder::new()
+    .prefix("vscch-judge-")
+    .tempdir()
+    .map_err(|e| anyhow!("无法创建临时文件夹：{}", e))?;
+  let program = temp.path().join(format!("program.{}", EXT));
+  compile(&args, &program)?;
+
+  let output = temp.path().join("program.stdout");
+  // 按显示宽度对齐，汉字等宽字符占两列
+  let width = cases
+    .iter()
+    .map(|c| c.name.width())
+    .max()
+    .unwrap()
+    .max("测试点".width());
+  println!();
+  println!(
+    "测试点{}  结果       时间       内存",
+    " ".repeat(width - "测试点".width())
+  );
+  let ansi = ansi_enabled();
+  let mut passed = 0;
+  for case in &cases {
+    let (verdict, usage, detail) = run_case(&args, &program, case, &output)?;
+    if verdict == Verdict::AC {
+      passed += 1;
+    }
+    let memory = usage
+      .memory
+      .map_or("-".to_string(), |m| format!("{:.1} MiB", m as f64 / 1024.0));
+    println!(
+      "{}{}  {}   {:>6} ms  {:>9}  {}",
+      case.name,
+      " ".repeat(width - case.name.width()),
+      if ansi {
+        verdict.colored()
+      } else {
+        verdict.text()
+      },
+      usage.time.as_millis(),
+      memory,
+      detail
+    );
+  }
+  println!();
+  println!("通过 {}/{} 个测试点。", passed, cases.len());
+  Ok(if passed == cases.len() { 0 } else { 1 })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn judge_args(source: PathBuf, tests: Option<PathBuf>) -> JudgeArgs {
+    JudgeArgs {
+      source: source,
+      tests: tests,
+      time_limit: 1000,
+      memory_limit: 256,
+      directives: false,
+      auto_lib: AutoLibArgs {
+        auto_libs: false,
+        lib_rules: vec![],
+      },
+      command: vec![],
+    }
+  }
+
+  fn names(cases: &[Case]) -> Vec<&str> {
+    cases.iter().map(|c| c.name.as_str()).collect()
+  }
+
+  #[test]
+  fn test_natural_key() {
+    let mut names = vec!["a.10", "a.2", "a.1", "b"];
+    names.sort_by_key(|n| natural_key(n));
+    assert_eq!(names, vec!["b", "a.1", "a.2", "a.10"]);
+  }
+
+  #[test]
+  fn test_collect_cases() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in [
+      "a.1.in", "a.1.out", "a.10.in", "a.10.ans", "a.2.in", "a.2.out", "a.x.in", "a.x.out",
+      "a.3.in",
+    ] {
+      File::create(dir.path().join(name)).unwrap();
+    }
+    let cases = collect_cases(&judge_args(dir.path().join("a.cpp"), None)).unwrap();
+    assert_eq!(names(&cases), vec!["a.1", "a.2", "a.10"]);
+    assert_eq!(cases[2].answer, dir.path().join("a.10.ans"));
+
+    // 没有 name.N.in 时使用 tests 文件夹
+    let tests = dir.path().join("tests");
+    fs::create_dir(&tests).unwrap();
+    for name in ["2.in", "2.out", "1.in", "1.out"] {
+      File::create(tests.join(name)).unwrap();
+    }
+    let cases = collect_cases(&judge_args(dir.path().join("b.cpp"), None)).unwrap();
+    assert_eq!(names(&cases), vec!["1", "2"]);
+    let cases = collect_cases(&judge_args(dir.path().join("a.cpp"), Some(tests))).unwrap();
+    assert_eq!(names(&cases), vec!["1", "2"]);
+  }
+}
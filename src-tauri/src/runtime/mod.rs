@@ -0,0 +1,27 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+// 由生成的 VS Code 任务调用的子命令。它们不写日志文件，直接以子进程的返回值退出。
+
+pub mod autolib;
+pub mod build;
+pub mod directive;
+pub mod io;
+pub mod judge;
+pub mod modules;
+pub mod pch;
+pub mod run;
@@ -0,0 +1,507 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+// C++20 模块：在编译主文件之前，按依赖顺序预编译 std 模块与工作区中的模块单元。
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Result};
+use clap::{ArgEnum, Args};
+use regex::Regex;
+
+use crate::tasks::is_link_arg;
+
+/// 模块单元的扩展名
+pub static UNIT_EXTS: &[&str] = &["cppm", "ixx"];
+
+#[derive(ArgEnum, Clone, Copy, PartialEq)]
+pub enum ModuleCompiler {
+  Gcc,
+  Clang,
+  Msvc,
+}
+
+#[derive(Args)]
+pub struct ModuleArgs {
+  /// 启用模块支持，并指定编译器的种类
+  #[clap(long, arg_enum, requires = "module-dir")]
+  pub modules: Option<ModuleCompiler>,
+
+  /// 查找模块单元（.cppm 与 .ixx）的文件夹，含子文件夹
+  #[clap(long)]
+  pub module_dir: Option<PathBuf>,
+
+  /// 存放预编译模块的文件夹
+  #[clap(long, default_value = "build/modules")]
+  pub module_cache: PathBuf,
+
+  /// 标准库 std 模块的源文件，如 libstdc++ 的 std.cc、libc++ 的 std.cppm 或 MSVC 的 std.ixx
+  #[clap(long)]
+  pub std_module: Option<PathBuf>,
+}
+
+struct Unit {
+  path: PathBuf,
+  /// 接口单元或分区提供的模块名；实现单元为 None
+  provides: Option<String>,
+  /// 未导出的分区（module M:part;）。MSVC 须以 /internalPartition 编译
+  internal: bool,
+  imports: Vec<String>,
+}
+
+impl Unit {
+  /// 输出文件的文件名（不含扩展名）。Clang 按此规则在 -fprebuilt-module-path 中查找
+  fn stem(&self) -> String {
+    match &self.provides {
+      Some(name) => name.replace(':', "-"),
+      None => format!("{}-impl", self.path.file_stem().unwrap().to_string_lossy()),
+    }
+  }
+}
+
+fn collect(dir: &Path, cache: &Path, result: &mut Vec<PathBuf>) -> Result<()> {
+  let entries = fs::read_dir(dir).map_err(|e| anyhow!("无法读取文件夹 {:?}：{}", dir, e))?;
+  for entry in entries {
+    let path = entry?.path();
+    let hidden = path
+      .file_name()
+      .map_or(false, |n| n.to_string_lossy().starts_with('.'));
+    if hidden || path == cache {
+      continue;
+    }
+    if path.is_dir() {
+      collect(&path, cache, result)?;
+    } else if path
+      .extension()
+      .map_or(false, |e| UNIT_EXTS.iter().any(|x| e == *x))
+    {
+      result.push(path);
+    }
+  }
+  Ok(())
+}
+
+/// 源文件导入的模块。分区 :part 补全为 M:part
+fn imports(content: &str, module: Option<&str>) -> Vec<String> {
+  let re = Regex::new(r"(?m)^\s*(?:export\s+)?import\s+([\w.:]+)\s*;").unwrap();
+  re.captures_iter(content)
+    .map(|c| {
+      let name = c[1].to_string();
+      match (name.strip_prefix(':'), module) {
+        (Some(part), Some(module)) => format!("{}:{}", module, part),
+        _ => name,
+      }
+    })
+    .collect()
+}
+
+/// 源文件的内容。源文件可能是 GBK 等编码，模块声明只含 ASCII 字符，故不要求合法的 UTF-8
+fn read_lossy(path: &Path) -> Result<String> {
+  let content = fs::read(path).map_err(|e| anyhow!("无法读取 {:?}：{}", path, e))?;
+  Ok(String::from_utf8_lossy(&content).to_string())
+}
+
+fn parse(path: PathBuf) -> Result<Unit> {
+  let content = read_lossy(&path)?;
+  let re = Regex::new(r"(?m)^\s*(export\s+)?module\s+([\w.]+)(:[\w.]+)?\s*;").unwrap();
+  let mut internal = false;
+  let (module, provides, implementation_of) = match re.captures(&content) {
+    Some(c) => {
+      let module = c[2].to_string();
+      let exported = c.get(1).is_some();
+      match c.get(3) {
+        // 分区总是提供 CMI，无论是否导出
+        Some(part) => {
+          internal = !exported;
+          let provides = format!("{}{}", module, part.as_str());
+          (Some(module), Some(provides), None)
+        }
+        None if exported => (Some(module.clone()), Some(module), None),
+        None => (Some(module.clone()), None, Some(module)),
+      }
+    }
+    None => (None, None, None),
+  };
+  let mut imports = imports(&content, module.as_deref());
+  // 实现单元隐式导入其模块
+  imports.extend(implementation_of);
+  Ok(Unit {
+    path: path,
+    provides: provides,
+    internal: internal,
+    imports: imports,
+  })
+}
+
+/// 按依赖排序，被导入的单元在前
+fn sort(units: Vec<Unit>) -> Result<Vec<Unit>> {
+  let mut remaining = units;
+  let mut sorted: Vec<Unit> = vec![];
+  while !remaining.is_empty() {
+    let provided_later =
+      |name: &String| remaining.iter().any(|u| u.provides.as_ref() == Some(name));
+    let ready = remaining
+      .iter()
+      .position(|u| !u.imports.iter().any(|i| provided_later(i)));
+    match ready {
+      Some(i) => sorted.push(remaining.remove(i)),
+      None => {
+        let names: Vec<_> = remaining
+          .iter()
+          .map(|u| u.path.display().to_string())
+          .collect();
+        return Err(anyhow!("模块之间存在循环依赖：{}", names.join("、")));
+      }
+    }
+  }
+  Ok(sorted)
+}
+
+fn imports_std(paths: &[&Path]) -> bool {
+  paths.iter().any(|p| {
+    read_lossy(p).map_or(false, |content| {
+      imports(&content, None)
+        .iter()
+        .any(|i| i == "std" || i == "std.compat")
+    })
+  })
+}
+
+/// 编译命令中去除源文件、输出与链接选项后的编译选项
+pub fn compile_flags(rest: &[String], sources: &[&Path]) -> Vec<String> {
+  let mut result = vec![];
+  let mut iter = rest.iter();
+  while let Some(arg) = iter.next() {
+    if arg == "-o" || arg == "/Fe:" {
+      iter.next();
+      continue;
+    }
+    if arg.eq_ignore_ascii_case("/link") {
+      break;
+    }
+    let path = Path::new(arg);
+    let is_source = sources.contains(&path)
+      || (path.is_file()
+        && path
+          .extension()
+          .map_or(false, |e| ["c", "cpp", "cc", "cxx"].iter().any(|x| e == *x)));
+    if arg.starts_with("/Fe") || is_link_arg(arg) || is_source {
+      continue;
+    }
+    result.push(arg.clone());
+  }
+  result
+}
+
+pub(crate) fn modified(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// 编译器的版本信息。编译器升级后路径可能不变，故一并计入。
+/// MSVC 不认识 --version，但会在标准错误中输出版本横幅
+pub fn version_text(compiler: &str) -> Result<String> {
+  let output = Command::new(compiler)
+    .arg("--version")
+    .output()
+    .map_err(|e| anyhow!("无法启动编译器 {}：{}", compiler, e))?;
+  Ok(format!(
+    "{}{}",
+    String::from_utf8_lossy(&output.stdout),
+    String::from_utf8_lossy(&output.stderr)
+  ))
+}
+
+fn run_compiler(compiler: &str, args: &[String]) -> Result<()> {
+  let status = Command::new(compiler)
+    .args(args)
+    .status()
+    .map_err(|e| anyhow!("无法启动编译器 {}：{}", compiler, e))?;
+  if !status.success() {
+    return Err(anyhow!("模块编译失败。"));
+  }
+  Ok(())
+}
+
+struct Builder<'a> {
+  kind: ModuleCompiler,
+  compiler: &'a str,
+  flags: Vec<String>,
+  cache: PathBuf,
+}
+
+impl Builder<'_> {
+  fn path(&self, stem: &str, ext: &str) -> String {
+    self
+      .cache
+      .join(format!("{}.{}", stem, ext))
+      .display()
+      .to_string()
+  }
+
+  fn object(&self, stem: &str) -> String {
+    self.path(
+      stem,
+      if self.kind == ModuleCompiler::Msvc {
+        "obj"
+      } else {
+        "o"
+      },
+    )
+  }
+
+  /// 编译一个单元，生成其目标文件与（接口单元与分区的）预编译模块
+  fn build(&self, source: &Path, stem: &str, interface: bool, internal: bool) -> Result<()> {
+    let source = source.display().to_string();
+    let mut args = self.flags.clone();
+    match self.kind {
+      ModuleCompiler::Gcc => {
+        args.extend(["-fmodules-ts", "-x", "c++", "-c", &source, "-o"].map(String::from));
+        args.push(self.object(stem));
+      }
+      ModuleCompiler::Clang if interface => {
+        let pcm = self.path(stem, "pcm");
+        let mut precompile = args.clone();
+        if stem == "std" {
+          // libc++ 的 std 模块以保留的名称声明
+          precompile.push("-Wno-reserved-module-identifier".to_string());
+        }
+        precompile.push(format!("-fprebuilt-module-path={}", self.cache.display()));
+        precompile
+          .extend(["-x", "c++-module", "--precompile", &source, "-o", &pcm].map(String::from));
+        run_compiler(self.compiler, &precompile)?;
+        args.extend(["-c".to_string(), pcm, "-o".to_string(), self.object(stem)]);
+      }
+      ModuleCompiler::Clang => {
+        args.push(format!("-fprebuilt-module-path={}", self.cache.display()));
+        args.extend(["-x", "c++", "-c", &source, "-o"].map(String::from));
+        args.push(self.object(stem));
+      }
+      ModuleCompiler::Msvc => {
+        args.extend(["/c", "/TP", "/ifcSearchDir"].map(String::from));
+        args.push(self.cache.display().to_string());
+        if internal {
+          args.push("/internalPartition".to_string());
+          args.push("/ifcOutput".to_string());
+          args.push(self.path(stem, "ifc"));
+        } else if interface {
+          args.push("/interface".to_string());
+          args.push("/ifcOutput".to_string());
+          args.push(self.path(stem, "ifc"));
+        }
+        args.push(format!("/Fo:{}", self.object(stem)));
+        args.push(source);
+      }
+    }
+    run_compiler(self.compiler, &args)
+  }
+
+  /// 编译主文件时追加的选项
+  fn final_args(&self, objects: Vec<String>) -> Vec<String> {
+    let mut args = match self.kind {
+      ModuleCompiler::Gcc => vec!["-fmodules-ts".to_string()],
+      ModuleCompiler::Clang => vec![format!("-fprebuilt-module-path={}", self.cache.display())],
+      ModuleCompiler::Msvc => vec![
+        "/ifcSearchDir".to_string(),
+        self.cache.display().to_string(),
+      ],
+    };
+    args.extend(objects);
+    args
+  }
+}
+
+/// 预编译模块，返回编译主文件时追加的选项（含各模块的目标文件）
+pub fn prepare(
+  args: &ModuleArgs,
+  compiler: &str,
+  rest: &[String],
+  sources: &[&Path],
+) -> Result<Vec<String>> {
+  let kind = match args.modules {
+    Some(kind) => kind,
+    None => return Ok(vec![]),
+  };
+  let flags = compile_flags(rest, sources);
+  // 编译器版本或编译选项不同的预编译模块互不兼容，故分开存放
+  let mut hasher = DefaultHasher::new();
+  compiler.hash(&mut hasher);
+  version_text(compiler)?.hash(&mut hasher);
+  flags.hash(&mut hasher);
+  let hash = format!("{:016x}", hasher.finish());
+  let cache = args.module_cache.join(&hash);
+  fs::create_dir_all(&cache).map_err(|e| anyhow!("无法创建文件夹 {:?}：{}", cache, e))?;
+  // GCC 将预编译模块统一放在当前文件夹的 gcm.cache 中，无法按选项区分。
+  // 记录上次编译所用的选项，选项改变时全部重新编译
+  let stamp = args.module_cache.join("gcc-flags");
+  let mut rebuilt =
+    kind == ModuleCompiler::Gcc && fs::read_to_string(&stamp).map_or(true, |s| s != hash);
+  let builder = Builder {
+    kind: kind,
+    compiler: compiler,
+    flags: flags,
+    cache: cache,
+  };
+
+  let mut paths = vec![];
+  if let Some(dir) = &args.module_dir {
+    collect(dir, &args.module_cache, &mut paths)?;
+  }
+  paths.sort();
+  let units = sort(paths.into_iter().map(parse).collect::<Result<Vec<_>>>()?)?;
+
+  let mut objects = vec![];
+  let scanned: Vec<&Path> = units
+    .iter()
+    .map(|u| u.path.as_path())
+    .chain(sources.iter().copied())
+    .collect();
+  if let Some(std_module) = args.std_module.as_ref().filter(|_| imports_std(&scanned)) {
+    let object = builder.object("std");
+    if rebuilt || modified(Path::new(&object)).is_none() {
+      println!("编译标准库模块 std，这可能需要一些时间...");
+      builder.build(std_module, "std", true, false)?;
+      rebuilt = true;
+    }
+    objects.push(object);
+  }
+  for unit in &units {
+    let object = builder.object(&unit.stem());
+    let stale = match (modified(&unit.path), modified(Path::new(&object))) {
+      (Some(source), Some(output)) => source > output,
+      _ => true,
+    };
+    if rebuilt || stale {
+      println!("编译模块单元 {}", unit.path.display());
+      builder.build(
+        &unit.path,
+        &unit.stem(),
+        unit.provides.is_some(),
+        unit.internal,
+      )?;
+      rebuilt = true;
+    }
+    objects.push(object);
+  }
+  if kind == ModuleCompiler::Gcc {
+    fs::write(&stamp, &hash)?;
+  }
+  Ok(builder.final_args(objects))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn unit(name: &str, provides: Option<&str>, imports: &[&str]) -> Unit {
+    Unit {
+      path: PathBuf::from(name),
+      provides: provides.map(String::from),
+      internal: false,
+      imports: imports.iter().map(|s| s.to_string()).collect(),
+    }
+  }
+
+  #[test]
+  fn test_imports() {
+    let content =
+      "import std;\nexport import :part;\n  import a.b ;\n// import c;\nimport <vector>;\n";
+    assert_eq!(imports(content, Some("m")), vec!["std", "m:part", "a.b"]);
+    assert_eq!(imports(content, None), vec!["std", ":part", "a.b"]);
+  }
+
+  #[test]
+  fn test_parse() {
+    let dir = tempfile::tempdir().unwrap();
+    let cases = [
+      (
+        "a.cppm",
+        "export module a;\nimport b;\n",
+        Some("a"),
+        false,
+        vec!["b"],
+      ),
+      (
+        "p.cppm",
+        "module;\n#include <cstdio>\nexport module a:p;\nimport :q;\n",
+        Some("a:p"),
+        false,
+        vec!["a:q"],
+      ),
+      ("q.cppm", "module a:q;\n", Some("a:q"), true, vec![]),
+      (
+        "impl.cppm",
+        "module a;\nimport std;\n",
+        None,
+        false,
+        vec!["std", "a"],
+      ),
+      ("plain.cppm", "import a;\n", None, false, vec!["a"]),
+    ];
+    for (name, content, provides, internal, expected) in cases {
+      let path = dir.path().join(name);
+      fs::write(&path, content).unwrap();
+      let unit = parse(path).unwrap();
+      assert_eq!(unit.provides.as_deref(), provides, "{}", name);
+      assert_eq!(unit.internal, internal, "{}", name);
+      assert_eq!(unit.imports, expected, "{}", name);
+    }
+
+    // 注释为 GBK 编码
+    let gbk = dir.path().join("gbk.cppm");
+    fs::write(
+      &gbk,
+      b"// \xc4\xe3\xba\xc3\nexport module g;\nimport std;\n",
+    )
+    .unwrap();
+    assert!(imports_std(&[gbk.as_path()]));
+    let unit = parse(gbk).unwrap();
+    assert_eq!(unit.provides.as_deref(), Some("g"));
+    assert!(!imports_std(&[dir.path().join("a.cppm").as_path()]));
+  }
+
+  #[test]
+  fn test_sort() {
+    let units = vec![
+      unit("main.cppm", None, &["a"]),
+      unit("a.cppm", Some("a"), &["a:p", "b"]),
+      unit("p.cppm", Some("a:p"), &["std"]),
+      unit("b.cppm", Some("b"), &[]),
+    ];
+    let sorted: Vec<_> = sort(units)
+      .unwrap()
+      .into_iter()
+      .map(|u| u.path.display().to_string())
+      .collect();
+    assert_eq!(sorted, vec!["p.cppm", "b.cppm", "a.cppm", "main.cppm"]);
+
+    let cyclic = vec![
+      unit("a.cppm", Some("a"), &["b"]),
+      unit("b.cppm", Some("b"), &["a"]),
+    ];
+    assert!(sort(cyclic).is_err());
+  }
+
+  #[test]
+  fn test_version_text() {
+    assert!(version_text("vscch-no-such-compiler").is_err());
+  }
+}
@@ -0,0 +1,178 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+// 预编译头文件：以与构建相同的编译选项预编译一个头文件，编译器或选项改变时自动重新生成。
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+
+use super::modules::{compile_flags, modified, version_text};
+
+#[derive(Args)]
+pub struct PchArgs {
+  /// 预编译的头文件，如 bits/stdc++.h；也可以是相对于当前文件夹的路径。
+  /// 其包含的工作区中的头文件修改后重新生成
+  #[clap(long)]
+  pub pch: Option<String>,
+
+  /// 存放预编译头文件的文件夹
+  #[clap(long, default_value = "build/pch")]
+  pub pch_cache: PathBuf,
+}
+
+/// 编译器以 -MMD 生成的依赖文件中列出的头文件。依赖文件为 Makefile 规则，
+/// 路径中的空格与 # 以反斜杠转义，$ 写作 $$，续行以反斜杠结尾
+fn dependencies(content: &str) -> Vec<PathBuf> {
+  let content = content.replace("\\\r\n", " ").replace("\\\n", " ");
+  // 规则的目标之后为 ": "；Windows 路径中盘符后的冒号后面不是空白
+  let deps = match content.find(": ") {
+    Some(i) => &content[i + 2..],
+    None => return vec![],
+  };
+  let mut result = vec![];
+  let mut current = String::new();
+  let mut chars = deps.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' if matches!(chars.peek(), Some(' ') | Some('#')) => current.push(chars.next().unwrap()),
+      '$' if chars.peek() == Some(&'$') => current.push(chars.next().unwrap()),
+      c if c.is_whitespace() => {
+        if !current.is_empty() {
+          result.push(PathBuf::from(std::mem::take(&mut current)));
+        }
+      }
+      c => current.push(c),
+    }
+  }
+  if !current.is_empty() {
+    result.push(PathBuf::from(current));
+  }
+  result
+}
+
+/// 包含目标头文件的包装头文件。工作区中的头文件以绝对路径包含，其余视为系统头文件
+fn wrapper_content(header: &str) -> Result<String> {
+  let path = Path::new(header);
+  Ok(if path.is_file() {
+    format!("#include \"{}\"\n", fs::canonicalize(path)?.display())
+  } else {
+    format!("#include <{}>\n", header)
+  })
+}
+
+/// 预编译头文件，返回编译源文件时追加的选项
+pub fn prepare(
+  args: &PchArgs,
+  compiler: &str,
+  rest: &[String],
+  sources: &[&Path],
+) -> Result<Vec<String>> {
+  let header = match &args.pch {
+    Some(header) => header,
+    None => return Ok(vec![]),
+  };
+  let name = Path::new(compiler)
+    .file_name()
+    .map_or(String::new(), |n| n.to_string_lossy().to_string());
+  let is_clang = name.contains("clang");
+  // 以驱动程序的名称区分语言，如 g++、clang++-17
+  let language = if name.contains("++") {
+    "c++-header"
+  } else {
+    "c-header"
+  };
+  let flags = compile_flags(rest, sources);
+  let wrapper_content = wrapper_content(header)?;
+  // 编译器路径、版本、选项或头文件不同时，预编译头文件不可复用
+  let mut hasher = DefaultHasher::new();
+  compiler.hash(&mut hasher);
+  version_text(compiler)?.hash(&mut hasher);
+  flags.hash(&mut hasher);
+  wrapper_content.hash(&mut hasher);
+  let dir = args.pch_cache.join(format!("{:016x}", hasher.finish()));
+  fs::create_dir_all(&dir).map_err(|e| anyhow!("无法创建文件夹 {:?}：{}", dir, e))?;
+
+  let wrapper = dir.join("pch.h");
+  // GCC 在包含 pch.h 时自动使用同一文件夹中的 pch.h.gch；Clang 须以 -include-pch 指定
+  let output = dir.join(if is_clang { "pch.h.pch" } else { "pch.h.gch" });
+  // 编译时记录包含的非系统头文件，其中任一修改或删除后重新生成
+  let depfile = dir.join("pch.d");
+  let stale = match (modified(&output), fs::read_to_string(&depfile)) {
+    (Some(time), Ok(content)) => dependencies(&content)
+      .iter()
+      .any(|d| modified(d).map_or(true, |m| m > time)),
+    _ => true,
+  };
+  if !wrapper.exists() {
+    fs::write(&wrapper, &wrapper_content)?;
+  }
+  if stale {
+    println!("预编译头文件 {}，这可能需要一些时间...", header);
+    let status = Command::new(compiler)
+      .args(&flags)
+      .args(["-x", language])
+      .arg(&wrapper)
+      .arg("-MMD")
+      .arg("-MF")
+      .arg(&depfile)
+      .arg("-o")
+      .arg(&output)
+      .status()
+      .map_err(|e| anyhow!("无法启动编译器 {}：{}", compiler, e))?;
+    if !status.success() {
+      let _ = fs::remove_file(&output);
+      return Err(anyhow!("预编译头文件 {} 失败。", header));
+    }
+  }
+  let mut result = if is_clang {
+    vec!["-include-pch".to_string(), output.display().to_string()]
+  } else {
+    vec!["-include".to_string(), wrapper.display().to_string()]
+  };
+  // 预编译头文件因选项不一致而无法使用时给出警告
+  result.push("-Winvalid-pch".to_string());
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_dependencies() {
+    let content = "/ws/build/pch/0/pch.h.gch: /ws/build/pch/0/pch.h /ws/src/common.h \\\n /ws/my\\ dir/a$$b.h \\\n  /ws/\\#x.h\n";
+    assert_eq!(
+      dependencies(content),
+      vec![
+        PathBuf::from("/ws/build/pch/0/pch.h"),
+        PathBuf::from("/ws/src/common.h"),
+        PathBuf::from("/ws/my dir/a$b.h"),
+        PathBuf::from("/ws/#x.h"),
+      ]
+    );
+    assert_eq!(
+      dependencies("C:\\ws\\pch.h.gch: C:\\ws\\pch.h\r\n"),
+      vec![PathBuf::from("C:\\ws\\pch.h")]
+    );
+  }
+}
@@ -0,0 +1,192 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+
+#[cfg(target_os = "linux")]
+use crate::utils::terminal;
+#[cfg(windows)]
+use crate::utils::winapi;
+
+#[derive(Args)]
+pub struct RunArgs {
+  /// 程序的工作目录
+  #[clap(long)]
+  pub cwd: Option<PathBuf>,
+
+  /// 在当前终端中运行，不打开新的终端窗口
+  #[clap(long)]
+  pub here: bool,
+
+  /// 为自身分配新的控制台窗口（Windows）。vscch 不是控制台程序，系统不会自动为其创建
+  #[clap(long, hide = true)]
+  pub new_console: bool,
+
+  /// 程序
+  pub program: String,
+
+  /// 程序的参数
+  #[clap(allow_hyphen_values = true)]
+  pub args: Vec<String>,
+}
+
+/// 等待用户按下任意键
+#[cfg(unix)]
+fn wait_key() {
+  use std::io::Read;
+  unsafe {
+    let mut term: libc::termios = std::mem::zeroed();
+    let is_tty = libc::tcgetattr(libc::STDIN_FILENO, &mut term) == 0;
+    let original = term;
+    if is_tty {
+      // 关闭行缓冲与回显，使单个按键即可返回
+      term.c_lflag &= !(libc::ICANON | libc::ECHO);
+      libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &term);
+      libc::tcflush(libc::STDIN_FILENO, libc::TCIFLUSH);
+    }
+    let _ = std::io::stdin().read(&mut [0u8]);
+    if is_tty {
+      libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+    }
+  }
+}
+
+#[cfg(windows)]
+fn wait_key() {
+  winapi::getch();
+}
+
+/// 在当前终端中运行程序，输出返回值与用时并等待按键
+fn run_here(args: &RunArgs) -> Result<i32> {
+  let mut command = Command::new(&args.program);
+  command.args(&args.args);
+  if let Some(cwd) = &args.cwd {
+    command.current_dir(cwd);
+  }
+  let start = Instant::now();
+  let status = command
+    .status()
+    .map_err(|e| anyhow!("无法启动 {}：{}", args.program, e))?;
+  let elapsed = start.elapsed();
+  let code = status.code().unwrap_or(1);
+  println!();
+  println!(
+    "\x1b[2m----------------\x1b[0m\n程序以返回值 {} 退出，用时 {:.3} 秒。按任意键关闭窗口...",
+    code,
+    elapsed.as_secs_f64()
+  );
+  wait_key();
+  Ok(code)
+}
+
+/// 以 --here 调用自身的参数
+fn here_args(args: &RunArgs, extra: &[&str]) -> Result<Vec<String>> {
+  let mut result = vec![
+    std::env::current_exe()?.to_string_lossy().to_string(),
+    "run".to_string(),
+    "--here".to_string(),
+  ];
+  result.extend(extra.iter().map(|a| a.to_string()));
+  if let Some(cwd) = &args.cwd {
+    result.push("--cwd".to_string());
+    result.push(cwd.to_string_lossy().to_string());
+  }
+  result.push(args.program.clone());
+  result.extend(args.args.iter().cloned());
+  Ok(result)
+}
+
+#[cfg(target_os = "linux")]
+fn open_terminal(args: &RunArgs) -> Result<bool> {
+  let terminal = match terminal::detect() {
+    Some(terminal) => terminal,
+    None => return Ok(false),
+  };
+  let mut command = Command::new(&terminal.path);
+  command.args(terminal.exec_args).args(here_args(args, &[])?);
+  if let Some(cwd) = &args.cwd {
+    command.current_dir(cwd);
+  }
+  command
+    .spawn()
+    .map_err(|e| anyhow!("无法启动终端 {:?}：{}", terminal.path, e))?;
+  Ok(true)
+}
+
+#[cfg(windows)]
+fn open_terminal(args: &RunArgs) -> Result<bool> {
+  // 直接启动自身，由 Command 负责参数的引号；新进程以 --new-console 自行分配控制台窗口
+  let command = here_args(args, &["--new-console"])?;
+  let (program, rest) = command.split_first().unwrap();
+  Command::new(program)
+    .args(rest)
+    .creation_flags(winapi::CREATE_NEW_CONSOLE)
+    .spawn()?;
+  Ok(true)
+}
+
+/// 将参数放入单引号中，作为 shell 命令的一部分
+#[cfg(target_os = "macos")]
+fn shell_quote(arg: &str) -> String {
+  format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+#[cfg(target_os = "macos")]
+fn open_terminal(args: &RunArgs) -> Result<bool> {
+  // Terminal.app 只能通过 AppleScript 执行一行 shell 命令
+  let line = here_args(args, &[])?
+    .iter()
+    .map(|a| shell_quote(a))
+    .collect::<Vec<_>>()
+    .join(" ");
+  let script = format!(
+    "tell application \"Terminal\"\nactivate\ndo script \"{}; exit\"\nend tell",
+    line.replace('\\', "\\\\").replace('"', "\\\"")
+  );
+  let status = Command::new("osascript").arg("-e").arg(script).status();
+  Ok(matches!(status, Ok(status) if status.success()))
+}
+
+pub fn run(args: RunArgs) -> Result<i32> {
+  if args.new_console {
+    // 启动时已附加到父进程的控制台，换成新的窗口
+    #[cfg(windows)]
+    {
+      winapi::alloc_console();
+      winapi::enable_virtual_terminal();
+    }
+  }
+  if args.here {
+    return run_here(&args);
+  }
+  if open_terminal(&args)? {
+    return Ok(0);
+  }
+  if cfg!(target_os = "linux") {
+    println!("找不到终端模拟器，将在当前终端中运行。可设置 TERMINAL 环境变量指定终端。");
+  } else if cfg!(target_os = "macos") {
+    println!("无法打开 Terminal.app，将在当前终端中运行。");
+  }
+  run_here(&args)
+}
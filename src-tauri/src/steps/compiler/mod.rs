@@ -87,7 +87,7 @@ impl Deref for Id {
   }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum CompilerType {
   GCC,
   LLVM,
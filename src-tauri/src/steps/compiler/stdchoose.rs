@@ -17,6 +17,8 @@
 
 use version_compare::Version;
 
+use super::CompilerType;
+
 pub fn gcc(ver: &str) -> (&'static str, &'static str) {
   let ver = Version::from(ver);
   if ver < Version::from("4.8") {
@@ -50,3 +52,168 @@ pub fn clang(ver: &str) -> (&'static str, &'static str) {
   }
 }
 
+
+/// 语言标准的规范名称与是否为 GNU 方言，如 gnu++2a => ("c++20", true)
+pub fn normalize(standard: &str) -> (String, bool) {
+  let standard = standard.to_ascii_lowercase();
+  let (standard, gnu) = match standard.strip_prefix("gnu") {
+    Some(rest) => (format!("c{}", rest), true),
+    None => (standard, false),
+  };
+  let canonical = match standard.as_str() {
+    "c++0x" => "c++11",
+    "c++1y" => "c++14",
+    "c++1z" => "c++17",
+    "c++2a" => "c++20",
+    "c++2b" => "c++23",
+    "c++2c" => "c++26",
+    "c90" => "c89",
+    "c9x" => "c99",
+    "c1x" => "c11",
+    "c18" => "c17",
+    "c2x" => "c23",
+    s => s,
+  };
+  (canonical.to_string(), gnu)
+}
+
+/// 规范名称、草案时期的名称、支持草案名称的最低版本、支持规范名称的最低版本。
+/// 草案名称在之后的版本中仍然可用
+type Spelling = (&'static str, &'static str, &'static str, &'static str);
+
+static GCC_SPELLINGS: &[Spelling] = &[
+  ("c++98", "c++98", "0", "0"),
+  ("c++03", "c++03", "0", "0"),
+  ("c++11", "c++0x", "4.3", "4.7"),
+  ("c++14", "c++1y", "4.8", "5.0"),
+  ("c++17", "c++1z", "5.0", "8.0"),
+  ("c++20", "c++2a", "8.0", "10.0"),
+  ("c++23", "c++2b", "11.0", "11.0"),
+  ("c++26", "c++2c", "14.0", "14.0"),
+  ("c89", "c89", "0", "0"),
+  ("c99", "c99", "0", "0"),
+  ("c11", "c1x", "4.6", "4.7"),
+  ("c17", "c17", "8.0", "8.0"),
+  ("c23", "c2x", "9.0", "14.0"),
+];
+
+static CLANG_SPELLINGS: &[Spelling] = &[
+  ("c++98", "c++98", "0", "0"),
+  ("c++03", "c++03", "0", "0"),
+  ("c++11", "c++0x", "3.0", "3.3"),
+  ("c++14", "c++1y", "3.4", "3.5"),
+  ("c++17", "c++1z", "3.5", "5.0"),
+  ("c++20", "c++2a", "5.0", "10.0"),
+  ("c++23", "c++2b", "13.0", "17.0"),
+  ("c++26", "c++2c", "17.0", "19.0"),
+  ("c89", "c89", "0", "0"),
+  ("c99", "c99", "0", "0"),
+  ("c11", "c1x", "3.0", "3.1"),
+  ("c17", "c17", "6.0", "6.0"),
+  ("c23", "c2x", "9.0", "18.0"),
+];
+
+/// MSVC 以 Visual Studio 的版本区分。尚未定稿的标准只能使用 c++latest 与 clatest
+static MSVC_SPELLINGS: &[Spelling] = &[
+  ("c++14", "c++14", "15.3", "15.3"),
+  ("c++17", "c++17", "15.3", "15.3"),
+  ("c++20", "c++latest", "15.0", "16.11"),
+  ("c++23", "c++latest", "15.0", "99.0"),
+  ("c++26", "c++latest", "15.0", "99.0"),
+  ("c11", "c11", "16.8", "16.8"),
+  ("c17", "c17", "16.8", "16.8"),
+  ("c23", "clatest", "17.9", "99.0"),
+];
+
+/// MSVC 没有对应选项的标准：C++14 之前的 C++ 按 C++14 编译，C89 与 C99 按默认方式编译
+static MSVC_WITHOUT_SWITCH: &[&str] = &["c++98", "c++03", "c++11", "c89", "c99"];
+
+fn spellings(ty: CompilerType) -> &'static [Spelling] {
+  match ty {
+    CompilerType::GCC => GCC_SPELLINGS,
+    CompilerType::LLVM => CLANG_SPELLINGS,
+    CompilerType::MSVC => MSVC_SPELLINGS,
+  }
+}
+
+/// 编译器默认选用的标准
+pub fn default_standard(ty: CompilerType, ver: &str, is_c: bool) -> &'static str {
+  let (cpp, c) = match ty {
+    CompilerType::GCC => gcc(ver),
+    CompilerType::LLVM => clang(ver),
+    CompilerType::MSVC => ("c++20", "c17"),
+  };
+  if is_c {
+    c
+  } else {
+    cpp
+  }
+}
+
+/// 编译器命令行中的标准名称（不含 -std= 或 /std:）。编译器不支持时返回 None
+pub fn spelling(ty: CompilerType, ver: &str, standard: &str, gnu: bool) -> Option<String> {
+  let ver = Version::from(ver)?;
+  let (_, draft, draft_since, since) = spellings(ty).iter().find(|s| s.0 == standard)?;
+  let name = if ver >= Version::from(since)? {
+    standard
+  } else if ver >= Version::from(draft_since)? {
+    draft
+  } else {
+    return None;
+  };
+  if gnu && ty != CompilerType::MSVC {
+    // c++20 => gnu++20，c17 => gnu17
+    Some(format!("gnu{}", &name[1..]))
+  } else {
+    Some(name.to_string())
+  }
+}
+
+/// 编译器认识、但没有对应选项的标准。此时保留所选标准，不添加选项
+pub fn without_switch(ty: CompilerType, standard: &str) -> bool {
+  ty == CompilerType::MSVC && MSVC_WITHOUT_SWITCH.contains(&standard)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_normalize() {
+    assert_eq!(normalize("C++2a"), ("c++20".to_string(), false));
+    assert_eq!(normalize("gnu++1z"), ("c++17".to_string(), true));
+    assert_eq!(normalize("gnu18"), ("c17".to_string(), true));
+    assert_eq!(normalize("c90"), ("c89".to_string(), false));
+    assert_eq!(normalize("c++23"), ("c++23".to_string(), false));
+  }
+
+  #[test]
+  fn test_spelling() {
+    let gcc = |ver, std, gnu| spelling(CompilerType::GCC, ver, std, gnu);
+    assert_eq!(gcc("9.2.0", "c++20", false), Some("c++2a".to_string()));
+    assert_eq!(gcc("12.2.0", "c++20", true), Some("gnu++20".to_string()));
+    assert_eq!(gcc("12.2.0", "c++26", false), None);
+    assert_eq!(gcc("13.1.0", "c23", true), Some("gnu2x".to_string()));
+    assert_eq!(
+      spelling(CompilerType::LLVM, "18.1.0", "c++26", false),
+      Some("c++2c".to_string())
+    );
+    assert_eq!(
+      spelling(CompilerType::MSVC, "17.5.3", "c++20", true),
+      Some("c++20".to_string())
+    );
+    assert_eq!(
+      spelling(CompilerType::MSVC, "16.8.0", "c++20", false),
+      Some("c++latest".to_string())
+    );
+    assert_eq!(spelling(CompilerType::MSVC, "17.8.0", "c23", false), None);
+  }
+
+  #[test]
+  fn test_without_switch() {
+    assert!(without_switch(CompilerType::MSVC, "c99"));
+    assert!(without_switch(CompilerType::MSVC, "c++11"));
+    assert!(!without_switch(CompilerType::MSVC, "c23"));
+    assert!(!without_switch(CompilerType::GCC, "c99"));
+  }
+}
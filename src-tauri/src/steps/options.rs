@@ -19,16 +19,251 @@ use serde::Deserialize;
 #[cfg(windows)]
 use crate::utils::winapi::get_acp;
 
+/// 构建任务编译哪些源文件
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum BuildMode {
+  /// 仅编译当前文件
+  Single,
+  /// 编译当前文件所在文件夹下的全部源文件
+  Folder,
+  /// 编译工作区内（含子文件夹）的全部源文件
+  Workspace,
+  /// 由 CMake Tools 扩展按 CMakeLists.txt 构建
+  #[serde(rename = "cmake")]
+  CMake,
+  /// 生成 Makefile，由 make 增量构建
+  Make,
+}
+
+impl Default for BuildMode {
+  fn default() -> Self {
+    BuildMode::Single
+  }
+}
+
+/// 构建的优化与调试信息配置
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum BuildVariant {
+  /// 不优化，带调试信息
+  Debug,
+  /// 开启优化，关闭断言
+  Release,
+}
+
+impl Default for BuildVariant {
+  fn default() -> Self {
+    BuildVariant::Debug
+  }
+}
+
+/// 提供代码补全与诊断的扩展
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum IntelliSense {
+  /// ms-vscode.cpptools 自带的 IntelliSense
+  #[serde(rename = "cpptools")]
+  CppTools,
+  /// clangd 语言服务器
+  Clangd,
+}
+
+impl Default for IntelliSense {
+  fn default() -> Self {
+    IntelliSense::CppTools
+  }
+}
+
+/// LLVM 编译器使用的调试器
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum Debugger {
+  /// vadimcn.vscode-lldb 扩展，自带 LLDB
+  #[serde(rename = "codelldb")]
+  CodeLldb,
+  /// LLVM 提供的 lldb-dap 与 llvm-vs-code-extensions.lldb-dap 扩展
+  #[serde(rename = "lldb-dap")]
+  LldbDap,
+  /// C/C++ 扩展的 cppdbg 与 GDB
+  #[serde(rename = "gdb")]
+  Gdb,
+}
+
+impl Default for Debugger {
+  fn default() -> Self {
+    Debugger::CodeLldb
+  }
+}
+
+/// 编译时启用的警告
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum WarningLevel {
+  /// 关闭全部警告
+  None,
+  /// 编译器的默认设置
+  Default,
+  /// -Wall -Wextra，MSVC 为 /W4
+  Extra,
+  /// 在 Extra 的基础上视警告为错误
+  Strict,
+}
+
+impl Default for WarningLevel {
+  fn default() -> Self {
+    WarningLevel::Default
+  }
+}
+
+/// 编译器诊断信息是否带颜色
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticsColor {
+  /// 由编译器根据输出是否为终端决定
+  Auto,
+  Always,
+  Never,
+}
+
+impl Default for DiagnosticsColor {
+  fn default() -> Self {
+    DiagnosticsColor::Auto
+  }
+}
+
+/// 运行与调试时程序的输入输出所在之处
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum RunLocation {
+  /// VS Code 的集成终端
+  Integrated,
+  /// 新的终端窗口
+  External,
+  /// 调试控制台；运行任务则使用任务的输出面板
+  Internal,
+}
+
+impl Default for RunLocation {
+  fn default() -> Self {
+    RunLocation::External
+  }
+}
+
+impl RunLocation {
+  /// launch.json 中 console 属性的取值
+  pub fn console(&self) -> &'static str {
+    match self {
+      RunLocation::Integrated => "integratedTerminal",
+      RunLocation::External => "externalTerminal",
+      RunLocation::Internal => "internalConsole",
+    }
+  }
+}
+
+/// 通过 gdbserver 在另一台机器或容器中调试
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteDebug {
+  /// ssh 的目标主机（可含用户名）或 docker 容器名
+  pub host: String,
+  /// gdbserver 监听的端口
+  pub port: u16,
+  /// 程序在远程机器上所在的文件夹
+  pub dir: String,
+  /// 复制程序并启动 gdbserver 的命令：ssh、docker 或自定义的命令模板
+  pub command: String,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Options {
   pub run_hotkey: String,
-  pub compatible_mode: bool,
+  #[serde(default)]
+  pub run_location: RunLocation,
   #[serde(rename = "activeLanguage")]
   pub language: String,
   #[serde(rename = "activeStandard")]
   pub standard: Option<String>,
   pub args: Vec<String>,
+  #[serde(default)]
+  pub warning_level: WarningLevel,
+  /// 严格遵循语言标准，拒绝编译器扩展
+  #[serde(default)]
+  pub pedantic: bool,
+  /// 使用 GNU 方言，如 -std=gnu++20；MSVC 不可用
+  #[serde(default)]
+  pub use_gnu: bool,
+  /// 以 GBK 编码输出字符串字面量，适用于简体中文 Windows 的控制台
+  #[serde(default)]
+  pub acp_output: bool,
+  #[serde(default)]
+  pub diagnostics_color: DiagnosticsColor,
+  /// 头文件搜索路径，同时加入 IntelliSense 配置
+  #[serde(default)]
+  pub include_dirs: Vec<String>,
+  /// 库搜索路径
+  #[serde(default)]
+  pub lib_dirs: Vec<String>,
+  /// 链接的库，如 m 对应 -lm（MSVC 为 m.lib）
+  #[serde(default)]
+  pub libs: Vec<String>,
+  /// 预处理宏定义，形如 NAME 或 NAME=VALUE
+  #[serde(default)]
+  pub defines: Vec<String>,
+  /// 构建前按依赖顺序预编译 C++20 模块单元与 std 模块
+  #[serde(default)]
+  pub modules: bool,
+  /// 预编译的头文件，如 bits/stdc++.h；为 None 时不使用预编译头文件
+  #[serde(default)]
+  pub pch: Option<String>,
+  #[serde(default)]
+  pub build_mode: BuildMode,
+  /// 运行任务使用的构建
+  #[serde(default)]
+  pub run_variant: BuildVariant,
+  /// 可执行文件的输出文件夹；为空时输出到源文件旁
+  #[serde(default = "default_output_dir")]
+  pub output_dir: String,
+  /// 生成或合并 .gitignore，排除构建输出
+  #[serde(default)]
+  pub gitignore: bool,
+  /// 多文件构建时排除的文件，glob 格式，相对于查找源文件的文件夹
+  #[serde(default)]
+  pub exclude: Vec<String>,
+  /// 构建时读取源文件开头指令注释中的编译选项
+  #[serde(default)]
+  pub directives: bool,
+  /// 构建时根据源文件包含的头文件追加链接库等选项
+  #[serde(default)]
+  pub auto_libs: bool,
+  /// 额外的头文件规则，形如 gmp.h=-lgmp
+  #[serde(default)]
+  pub lib_rules: Vec<String>,
+  /// 通过 pkg-config 查询编译选项的库
+  #[serde(default)]
+  pub libraries: Vec<String>,
+  #[serde(default)]
+  pub intellisense: IntelliSense,
+  /// 生成 .vscode/settings.json 中的编码、终端环境等设置
+  #[serde(default = "default_true")]
+  pub workspace_settings: bool,
+  /// 运行和调试时从 .in 文件读取输入，运行后与 .ans 文件比较
+  #[serde(default)]
+  pub io_files: bool,
+  /// 仅对 LLVM 编译器有效
+  #[serde(default)]
+  pub debugger: Debugger,
+  /// 调试时加载与编译器配套的 libstdc++ 美化输出脚本
+  #[serde(default = "default_true")]
+  pub gdb_pretty_printers: bool,
+  /// 调试时单步跳过标准库头文件
+  #[serde(default = "default_true")]
+  pub gdb_skip_std: bool,
+  /// 调试时按动态类型显示多态对象，并隐藏静态成员
+  #[serde(default = "default_true")]
+  pub gdb_print_object: bool,
+  #[serde(default)]
+  pub remote: Option<RemoteDebug>,
   pub ascii_check: bool,
   pub remove_extensions: bool,
   pub add_to_path: bool,
@@ -38,7 +273,23 @@ pub struct Options {
   pub collect_data: bool,
 }
 
+fn default_true() -> bool {
+  true
+}
+
+fn default_output_dir() -> String {
+  "${workspaceFolder}/build".to_string()
+}
+
 pub fn use_gnu_enabled(setup: &str) -> bool {
+  setup != "msvc"
+}
+
+pub fn debugger_enabled(setup: &str) -> bool {
+  ["llvm", "llvm-mingw", "apple"].contains(&setup)
+}
+
+pub fn gdb_enabled(setup: &str) -> bool {
   ["gcc-mingw", "gcc"].contains(&setup)
 }
 
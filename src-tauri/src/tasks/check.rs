@@ -0,0 +1,90 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::Result;
+use log::{debug, warn};
+use serde::Serialize;
+
+use super::{diff, extension, generated_files, TaskArgs};
+use crate::utils::ToString;
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Drift {
+  /// 配置文件缺失，或内容与期望不一致
+  File {
+    path: String,
+    missing: bool,
+    diff: String,
+  },
+  /// 应安装的扩展未安装（installed 为 false），或应卸载的扩展仍已安装
+  Extension { id: String, installed: bool },
+}
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+  pub passed: bool,
+  pub drifts: Vec<Drift>,
+}
+
+fn is_installed(installed: &[String], id: &str) -> bool {
+  // 扩展 ID 不区分大小写
+  installed.iter().any(|i| i.eq_ignore_ascii_case(id))
+}
+
+/// 在内存中重新生成配置，并与磁盘上的文件和已安装的扩展比较
+pub fn run(args: &TaskArgs) -> Result<Report> {
+  let mut drifts = vec![];
+
+  for (path, content) in generated_files(args)? {
+    let missing = !path.exists();
+    if let Some(diff) = diff::compare(&path, &content)? {
+      debug!("{:?} 与期望不一致。", path);
+      drifts.push(Drift::File {
+        path: path.to_string(),
+        missing: missing,
+        diff: diff,
+      });
+    }
+  }
+
+  let installed = extension::installed(args);
+  if installed.is_empty() {
+    warn!("未获取到已安装的扩展列表。若启用了 --skip-ext-manage，扩展检查结果不可信。");
+  }
+  for id in extension::required(args) {
+    if !is_installed(&installed, id) {
+      drifts.push(Drift::Extension {
+        id: id.to_string(),
+        installed: false,
+      });
+    }
+  }
+  for id in extension::unrecommended(args) {
+    if is_installed(&installed, id) {
+      drifts.push(Drift::Extension {
+        id: id.to_string(),
+        installed: true,
+      });
+    }
+  }
+
+  Ok(Report {
+    passed: drifts.is_empty(),
+    drifts: drifts,
+  })
+}
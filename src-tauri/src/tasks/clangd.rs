@@ -0,0 +1,94 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::debug;
+
+use super::{is_link_arg, write_file, Content, TaskArgs};
+use crate::steps::options::BuildMode;
+use crate::utils::ToString;
+
+fn compile_flags_path(args: &TaskArgs) -> PathBuf {
+  args.workspace.join("compile_flags.txt")
+}
+
+fn dot_clangd_path(args: &TaskArgs) -> PathBuf {
+  args.workspace.join(".clangd")
+}
+
+/// CMake 会自行导出 compile_commands.json，此时无需 compile_flags.txt
+fn uses_compile_flags(args: &TaskArgs) -> bool {
+  args.build_mode != BuildMode::CMake
+}
+
+/// 与构建任务相同的编译选项，每行一个
+fn compile_flags_content(args: &TaskArgs) -> String {
+  let mut flags = vec![];
+  if args.setup.is_msvc() {
+    flags.push("/EHsc".to_string());
+  }
+  flags.extend(args.args.iter().filter(|a| !is_link_arg(a)).cloned());
+  flags.push("".to_string());
+  flags.join("\n")
+}
+
+fn dot_clangd_content(args: &TaskArgs) -> String {
+  // Compiler 决定 clangd 的驱动模式（如 cl.exe），并与 --query-driver 配合获取系统头文件路径
+  let mut lines = vec![
+    "CompileFlags:".to_string(),
+    format!("  Compiler: {}", args.compiler_path.to_string()),
+  ];
+  if !uses_compile_flags(args) {
+    lines.push("  CompilationDatabase: build".to_string());
+  }
+  lines.push("".to_string());
+  lines.join("\n")
+}
+
+/// clangd 模式下将要生成的文件
+pub fn files(args: &TaskArgs) -> Vec<(PathBuf, Content)> {
+  let mut files = vec![];
+  if uses_compile_flags(args) {
+    files.push((
+      compile_flags_path(args),
+      Content::Text(compile_flags_content(args)),
+    ));
+  }
+  files.push((
+    dot_clangd_path(args),
+    Content::Text(dot_clangd_content(args)),
+  ));
+  files
+}
+
+pub fn compile_flags(args: &TaskArgs) -> Result<()> {
+  if !uses_compile_flags(args) {
+    debug!("使用 CMake 导出的 compile_commands.json，不生成 compile_flags.txt。");
+    return Ok(());
+  }
+  let content = compile_flags_content(args);
+  debug!("compile_flags.txt: {}", content);
+  write_file(args, compile_flags_path(args), Content::Text(content))
+}
+
+pub fn dot_clangd(args: &TaskArgs) -> Result<()> {
+  let content = dot_clangd_content(args);
+  debug!(".clangd: {}", content);
+  write_file(args, dot_clangd_path(args), Content::Text(content))
+}
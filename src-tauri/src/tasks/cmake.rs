@@ -0,0 +1,410 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde_json::json;
+use version_compare::Version;
+
+use super::dotvscode::{source_exts, PATH_SEPARATOR, PROJECT_PROGRAM};
+use super::{is_link_arg, write_file, Content, TaskArgs};
+use crate::utils::ToString;
+
+/// 用于识别由本工具生成的 CMakeLists.txt；不含此标记的文件视为用户自己的项目
+static GENERATED_MARK: &str = "# Generated by VSCodeConfigHelper";
+
+pub static BUILD_TASK_LABEL: &str = "cmake build";
+
+pub fn lists_path(args: &TaskArgs) -> PathBuf {
+  args.workspace.join("CMakeLists.txt")
+}
+
+pub fn presets_path(args: &TaskArgs) -> PathBuf {
+  args.workspace.join("CMakePresets.json")
+}
+
+/// 工作区中是否已有用户自己编写的 CMakeLists.txt
+pub fn user_lists_exists(args: &TaskArgs) -> bool {
+  let path = lists_path(args);
+  match fs::read_to_string(&path) {
+    Ok(content) => !content.starts_with(GENERATED_MARK),
+    Err(_) => false,
+  }
+}
+
+/// CMAKE_<LANG>_STANDARD 接受的值，如 c++20 => 20，c89 => 90
+fn standard_number(args: &TaskArgs) -> String {
+  let standard = args.standard.to_ascii_lowercase();
+  let number = standard.trim_start_matches(|c: char| !c.is_ascii_digit());
+  match number {
+    "89" => "90".into(),
+    "03" => "98".into(),
+    _ => number.into(),
+  }
+}
+
+/// 所需的最低 CMake 版本：预设文件第 3 版需要 3.21，CMAKE_CXX_STANDARD 26 需要 3.25
+fn minimum_version(args: &TaskArgs) -> (u32, u32) {
+  if !args.is_c && standard_number(args) == "26" {
+    (3, 25)
+  } else {
+    (3, 21)
+  }
+}
+
+/// 与 MSVC 所属 Visual Studio 主版本号对应的生成器
+fn vs_generator(version: &str) -> Option<&'static str> {
+  match version.split('.').next()? {
+    "15" => Some("Visual Studio 15 2017"),
+    "16" => Some("Visual Studio 16 2019"),
+    "17" => Some("Visual Studio 17 2022"),
+    "18" => Some("Visual Studio 18 2026"),
+    _ => None,
+  }
+}
+
+fn lists_content(args: &TaskArgs) -> String {
+  let lang = if args.is_c { "C" } else { "CXX" };
+  let std_prefix = if args.setup.is_msvc() {
+    "/std:"
+  } else {
+    "-std="
+  };
+  let project_name = args
+    .workspace
+    .file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .filter(|n| {
+      n.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+    .unwrap_or_else(|| PROJECT_PROGRAM.to_string());
+  // 语言标准由 CMAKE_<LANG>_STANDARD 指定，不再重复传递
+  let (link_args, compile_args): (Vec<_>, Vec<_>) = args
+    .args
+    .iter()
+    .filter(|a| !a.starts_with(std_prefix))
+    // target_link_libraries 中的选项直接交给链接器，无需 cl.exe 的 /link
+    .filter(|a| !a.eq_ignore_ascii_case("/link"))
+    .partition(|a| is_link_arg(a));
+  let globs = source_exts(args)
+    .iter()
+    .map(|ext| format!("\"${{CMAKE_SOURCE_DIR}}/*.{}\"", ext))
+    .collect::<Vec<_>>()
+    .join(" ");
+
+  let mut lines = vec![
+    GENERATED_MARK.to_string(),
+    "# 删除第一行后，本工具将不再覆盖此文件。".to_string(),
+    format!(
+      "cmake_minimum_required(VERSION {}.{})",
+      minimum_version(args).0,
+      minimum_version(args).1
+    ),
+    format!("project({} LANGUAGES {})", project_name, lang),
+    "".to_string(),
+    format!("set(CMAKE_{}_STANDARD {})", lang, standard_number(args)),
+    format!("set(CMAKE_{}_STANDARD_REQUIRED ON)", lang),
+    format!(
+      "set(CMAKE_{}_EXTENSIONS {})",
+      lang,
+      if args.use_gnu { "ON" } else { "OFF" }
+    ),
+    "".to_string(),
+    format!("file(GLOB_RECURSE SOURCES CONFIGURE_DEPENDS {})", globs),
+    "list(FILTER SOURCES EXCLUDE REGEX \"^${CMAKE_BINARY_DIR}/\")".to_string(),
+  ];
+  for pattern in &args.exclude {
+    lines.push(format!(
+      "list(FILTER SOURCES EXCLUDE REGEX \"{}\")",
+      glob_to_regex(pattern)
+    ));
+  }
+  lines.push(format!("add_executable({} ${{SOURCES}})", PROJECT_PROGRAM));
+  if !compile_args.is_empty() {
+    lines.push(format!(
+      "target_compile_options({} PRIVATE {})",
+      PROJECT_PROGRAM,
+      quote_all(&compile_args)
+    ));
+  }
+  if !link_args.is_empty() {
+    lines.push(format!(
+      "target_link_libraries({} PRIVATE {})",
+      PROJECT_PROGRAM,
+      quote_all(&link_args)
+    ));
+  }
+  lines.push("".to_string());
+  lines.join("\n")
+}
+
+fn quote_all(args: &[&String]) -> String {
+  args
+    .iter()
+    .map(|a| format!("\"{}\"", a.replace('"', "\\\"")))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// 将排除规则转换为 CMake 的正则表达式，匹配相对于源文件夹的路径
+fn glob_to_regex(pattern: &str) -> String {
+  let mut regex = String::from("^${CMAKE_SOURCE_DIR}/");
+  let mut chars = pattern.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '*' if chars.peek() == Some(&'*') => {
+        chars.next();
+        regex.push_str(".*");
+      }
+      '*' => regex.push_str("[^/]*"),
+      '?' => regex.push_str("[^/]"),
+      '.' | '+' | '(' | ')' | '^' | '$' | '[' | ']' | '|' | '\\' => {
+        regex.push('\\');
+        regex.push('\\');
+        regex.push(c);
+      }
+      _ => regex.push(c),
+    }
+  }
+  regex.push('$');
+  regex
+}
+
+fn presets_content(args: &TaskArgs) -> serde_json::Value {
+  let lang = if args.is_c { "C" } else { "CXX" };
+  let mut cache = json!({
+    "CMAKE_BUILD_TYPE": "Debug",
+    "CMAKE_EXPORT_COMPILE_COMMANDS": "ON"
+  });
+  let mut preset = json!({
+    "name": "vscch",
+    "displayName": format!("vscch ({})", args.setup.name),
+    "binaryDir": "${sourceDir}/build",
+  });
+  if args.setup.is_msvc() {
+    // Visual Studio 生成器自行查找 MSVC 环境，无需 vcvars64.bat。
+    // 安装了多个 Visual Studio 时，以 CMAKE_GENERATOR_INSTANCE 指定所选的一个
+    match vs_generator(&args.compiler_version) {
+      Some(generator) => preset["generator"] = json!(generator),
+      None => warn!(
+        "无法确定 Visual Studio {} 对应的 CMake 生成器，将由 CMake 自行选择。",
+        args.compiler_version
+      ),
+    }
+    // ...\VC\Tools\MSVC\<版本>\bin\HostX64\x64\cl.exe 往上 8 级为安装路径
+    if let Some(instance) = args.compiler_path.ancestors().nth(8) {
+      cache["CMAKE_GENERATOR_INSTANCE"] = json!(instance.to_string());
+    }
+    preset["architecture"] = json!({ "value": "x64", "strategy": "set" });
+  } else {
+    cache[format!("CMAKE_{}_COMPILER", lang)] = json!(args.compiler_path.to_string());
+    if args.setup.is_mingw() {
+      let path = args.compiler_path.parent().unwrap().to_string();
+      preset["generator"] = json!("MinGW Makefiles");
+      preset["environment"] = json!({
+        "PATH": format!("{}{}$penv{{PATH}}", path, PATH_SEPARATOR)
+      });
+    } else {
+      preset["generator"] = json!("Unix Makefiles");
+    }
+  }
+  preset["cacheVariables"] = cache;
+  let (major, minor) = minimum_version(args);
+  json!({
+    "version": 3,
+    "cmakeMinimumRequired": {
+      "major": major,
+      "minor": minor,
+      "patch": 0
+    },
+    "configurePresets": [preset],
+    "buildPresets": [
+      {
+        "name": "vscch",
+        "configurePreset": "vscch"
+      }
+    ]
+  })
+}
+
+fn version_of(json: &serde_json::Value) -> String {
+  let part = |key: &str| json[key].as_u64().unwrap_or(0);
+  format!("{}.{}.{}", part("major"), part("minor"), part("patch"))
+}
+
+/// 将生成的预设合并到已有的 CMakePresets.json 中：替换同名的预设，保留用户的其它预设
+fn merge_presets(
+  existing: serde_json::Value,
+  generated: serde_json::Value,
+) -> Result<serde_json::Value> {
+  let mut result = existing;
+  if !result.is_object() {
+    return Err(anyhow!("CMakePresets.json 的内容应为 object 类型。"));
+  }
+  let version = result["version"]
+    .as_u64()
+    .unwrap_or(0)
+    .max(generated["version"].as_u64().unwrap());
+  result["version"] = json!(version);
+  let required = &generated["cmakeMinimumRequired"];
+  let newer = match (
+    Version::from(&version_of(required)),
+    Version::from(&version_of(&result["cmakeMinimumRequired"])),
+  ) {
+    (Some(required), Some(existing)) => required > existing,
+    _ => true,
+  };
+  if newer {
+    result["cmakeMinimumRequired"] = required.clone();
+  }
+  for key in ["configurePresets", "buildPresets"] {
+    let mut presets: Vec<serde_json::Value> = result[key].as_array().cloned().unwrap_or_default();
+    for preset in generated[key].as_array().unwrap() {
+      match presets.iter_mut().find(|p| p["name"] == preset["name"]) {
+        Some(p) => *p = preset.clone(),
+        None => presets.push(preset.clone()),
+      }
+    }
+    result[key] = json!(presets);
+  }
+  Ok(result)
+}
+
+/// 合并后的 CMakePresets.json 内容
+fn presets_json(args: &TaskArgs) -> Result<serde_json::Value> {
+  let generated = presets_content(args);
+  let path = presets_path(args);
+  if !path.exists() {
+    return Ok(generated);
+  }
+  // JSON5 是 JSON 的超集，容许手动编辑留下的尾随逗号等
+  let existing: serde_json::Value = json5::from_str(&fs::read_to_string(&path)?)
+    .map_err(|e| anyhow!("无法解析已有的 CMakePresets.json：{}", e))?;
+  merge_presets(existing, generated)
+}
+
+/// 由 CMake Tools 提供的构建任务
+pub fn build_task(args: &TaskArgs) -> serde_json::Value {
+  let mut task = json!({
+    "type": "cmake",
+    "label": BUILD_TASK_LABEL,
+    "command": "build",
+    "group": {
+      "kind": "build",
+      "isDefault": true
+    },
+    "problemMatcher": []
+  });
+  // 用户自己的项目中目标名称未知，此时构建默认目标
+  if !user_lists_exists(args) {
+    task["targets"] = json!([PROJECT_PROGRAM]);
+  }
+  task
+}
+
+/// CMake 模式下将要生成的文件。用户自己的 CMakeLists.txt 不在其列
+pub fn files(args: &TaskArgs) -> Result<Vec<(PathBuf, Content)>> {
+  let mut files = vec![];
+  if !user_lists_exists(args) {
+    files.push((lists_path(args), Content::Text(lists_content(args))));
+  }
+  files.push((presets_path(args), Content::Json(presets_json(args)?)));
+  Ok(files)
+}
+
+pub fn cmake_lists(args: &TaskArgs) -> Result<()> {
+  if user_lists_exists(args) {
+    warn!("工作区中已有 CMakeLists.txt，将保留它。请确保其中定义了可执行目标。");
+    return Ok(());
+  }
+  let content = lists_content(args);
+  debug!("CMakeLists.txt: {}", content);
+  write_file(args, lists_path(args), Content::Text(content))
+}
+
+pub fn cmake_presets(args: &TaskArgs) -> Result<()> {
+  let json = presets_json(args)?;
+  debug!("CMakePresets.json: {}", json);
+  write_file(args, presets_path(args), Content::Json(json))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_glob_to_regex() {
+    assert_eq!(
+      glob_to_regex("test/*.cpp"),
+      "^${CMAKE_SOURCE_DIR}/test/[^/]*\\\\.cpp$"
+    );
+    assert_eq!(
+      glob_to_regex("**/a?.c"),
+      "^${CMAKE_SOURCE_DIR}/.*/a[^/]\\\\.c$"
+    );
+    assert_eq!(glob_to_regex("old"), "^${CMAKE_SOURCE_DIR}/old$");
+  }
+
+  #[test]
+  fn test_vs_generator() {
+    assert_eq!(
+      vs_generator("17.5.33424.131"),
+      Some("Visual Studio 17 2022")
+    );
+    assert_eq!(vs_generator("16.11.2"), Some("Visual Studio 16 2019"));
+    assert_eq!(vs_generator("14.0"), None);
+    assert_eq!(vs_generator(""), None);
+  }
+
+  #[test]
+  fn test_merge_presets() {
+    let generated = json!({
+      "version": 3,
+      "cmakeMinimumRequired": { "major": 3, "minor": 25, "patch": 0 },
+      "configurePresets": [{ "name": "vscch", "binaryDir": "${sourceDir}/build" }],
+      "buildPresets": [{ "name": "vscch", "configurePreset": "vscch" }]
+    });
+    let existing = json!({
+      "version": 4,
+      "cmakeMinimumRequired": { "major": 3, "minor": 21, "patch": 0 },
+      "configurePresets": [
+        { "name": "release", "binaryDir": "${sourceDir}/out" },
+        { "name": "vscch", "binaryDir": "old" }
+      ]
+    });
+    let merged = merge_presets(existing, generated.clone()).unwrap();
+    assert_eq!(merged["version"], 4);
+    assert_eq!(merged["cmakeMinimumRequired"]["minor"], 25);
+    assert_eq!(
+      merged["configurePresets"],
+      json!([
+        { "name": "release", "binaryDir": "${sourceDir}/out" },
+        { "name": "vscch", "binaryDir": "${sourceDir}/build" }
+      ])
+    );
+    assert_eq!(merged["buildPresets"], generated["buildPresets"]);
+
+    let newer = json!({ "version": 3, "cmakeMinimumRequired": { "major": 3, "minor": 28 } });
+    let merged = merge_presets(newer, generated).unwrap();
+    assert_eq!(merged["cmakeMinimumRequired"]["minor"], 28);
+    assert!(merge_presets(json!([]), json!({})).is_err());
+  }
+}
@@ -0,0 +1,124 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+use log::{debug, warn};
+
+use super::TaskArgs;
+use crate::steps::compiler::CompilerType;
+use crate::steps::options::Debugger;
+use crate::utils::ToString;
+
+/// 实际使用的调试器。GCC 总是使用 GDB，MSVC 总是使用 Visual Studio 调试器
+pub fn debugger(args: &TaskArgs) -> Option<Debugger> {
+  match args.setup.ty {
+    CompilerType::GCC => Some(Debugger::Gdb),
+    CompilerType::LLVM => Some(args.debugger),
+    CompilerType::MSVC => None,
+  }
+}
+
+/// launch.json 中调试配置的 type
+pub fn debug_type(args: &TaskArgs) -> &'static str {
+  match debugger(args) {
+    Some(Debugger::Gdb) => "cppdbg",
+    Some(Debugger::CodeLldb) => "lldb",
+    Some(Debugger::LldbDap) => "lldb-dap",
+    None => "cppvsdbg",
+  }
+}
+
+fn exe(name: &str) -> String {
+  if cfg!(windows) {
+    format!("{}.exe", name)
+  } else {
+    name.to_string()
+  }
+}
+
+/// 编译器旁的同名程序，否则为 PATH 中的程序
+fn find(args: &TaskArgs, names: &[String]) -> Option<PathBuf> {
+  let bin_path = args.compiler_path.parent().unwrap();
+  names
+    .iter()
+    .map(|name| bin_path.join(exe(name)))
+    .find(|p| p.exists())
+    .or_else(|| names.iter().find_map(|name| which::which(name).ok()))
+}
+
+/// lldb-dap 的路径。LLVM 18 之前它名为 lldb-vscode；
+/// Debian 等发行版以主版本号为后缀安装多个版本，须与编译器的版本对应
+pub fn lldb_dap_path(args: &TaskArgs) -> PathBuf {
+  let major = args.compiler_version.split('.').next().unwrap_or_default();
+  let mut names = vec![];
+  for name in ["lldb-dap", "lldb-vscode"] {
+    names.push(format!("{}-{}", name, major));
+    names.push(name.to_string());
+  }
+  match find(args, &names) {
+    Some(path) => {
+      debug!("lldb-dap 位于 {:?}", path);
+      path
+    }
+    None => {
+      warn!("找不到 lldb-dap，调试前请安装它并确保其位于 PATH 中。");
+      PathBuf::from(exe("lldb-dap"))
+    }
+  }
+}
+
+/// cppdbg 使用的调试器。GCC 的 GDB 与编译器在同一文件夹；Clang 则可能需要使用 PATH 中的 GDB
+pub fn mi_debugger_path(args: &TaskArgs) -> String {
+  let bin_path = args.compiler_path.parent().unwrap();
+  if args.setup.ty == CompilerType::GCC {
+    return bin_path.join(exe("gdb")).to_string();
+  }
+  match find(args, &["gdb".to_string()]) {
+    Some(path) => path.to_string(),
+    None => {
+      warn!("找不到 GDB，调试前请安装它并确保其位于 PATH 中。");
+      exe("gdb")
+    }
+  }
+}
+
+/// 检查程序崩溃时能否在工作目录中写出 core 文件，不能时给出警告
+#[cfg(target_os = "linux")]
+pub fn check_core_dump() {
+  let mut limit: libc::rlimit = unsafe { std::mem::zeroed() };
+  if unsafe { libc::getrlimit(libc::RLIMIT_CORE, &mut limit) } == 0 && limit.rlim_cur == 0 {
+    warn!("当前 core 文件大小限制（ulimit -c）为 0，程序崩溃时不会生成 core 文件。可在 ~/.bashrc 等处加入 ulimit -c unlimited。");
+  }
+  let pattern = match std::fs::read_to_string("/proc/sys/kernel/core_pattern") {
+    Ok(pattern) => pattern.trim().to_string(),
+    Err(_) => return,
+  };
+  debug!("core_pattern: {}", pattern);
+  if pattern.starts_with('|') {
+    // 如 systemd-coredump 与 apport
+    warn!(
+      "core_pattern 为 {}，core 文件会交给该程序处理而不会写入工作目录。若使用 systemd-coredump，可用 coredumpctl dump -o core 导出。",
+      pattern
+    );
+  } else if pattern.starts_with('/') {
+    warn!(
+      "core_pattern 为 {}，core 文件不会写入工作目录，调试时请填写实际路径。",
+      pattern
+    );
+  }
+}
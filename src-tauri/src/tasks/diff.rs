@@ -0,0 +1,156 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use log::{debug, trace};
+use serde::Serialize;
+use similar::TextDiff;
+
+use super::{generated_files, Content, TaskArgs};
+use crate::utils::ToString;
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FileDiff {
+  pub path: String,
+  /// 统一差异格式（unified diff）的文本
+  pub diff: String,
+}
+
+/// 去除空白差异：每行首尾空白去掉、连续空白合并，并丢弃空行
+fn normalize_text(text: &str) -> Vec<String> {
+  text
+    .lines()
+    .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+    .filter(|l| !l.is_empty())
+    .collect()
+}
+
+/// 比较磁盘上的文件与将要生成的内容。
+/// 若二者仅有空白或键序的差别，返回 None；否则返回差异文本。文件不存在时视为空文件。
+pub fn compare(path: &Path, content: &Content) -> Result<Option<String>> {
+  let old = if path.exists() {
+    fs::read_to_string(path)?
+  } else {
+    String::new()
+  };
+  let (old, new) = match content {
+    Content::Json(new) => {
+      // JSON5 是 vscode JSON with comment 的超集。直接用 serde_json 可能出错
+      match json5::from_str::<serde_json::Value>(&old) {
+        // serde_json 的对象按键排序，故比较与输出都不受键序影响
+        Ok(old) if &old == new => return Ok(None),
+        Ok(old) => (
+          serde_json::to_string_pretty(&old)?,
+          serde_json::to_string_pretty(new)?,
+        ),
+        // 无法解析（如手动编辑后留下语法错误）时按去除空白差异后的文本比较
+        Err(_) => {
+          let old = normalize_text(&old);
+          let new = normalize_text(&serde_json::to_string_pretty(new)?);
+          if old == new {
+            return Ok(None);
+          }
+          (old.join("\n") + "\n", new.join("\n") + "\n")
+        }
+      }
+    }
+    Content::Text(new) => {
+      if normalize_text(&old) == normalize_text(new) {
+        return Ok(None);
+      }
+      (old, new.clone())
+    }
+  };
+  let name = path.to_string();
+  let diff = TextDiff::from_lines(&old, &new)
+    .unified_diff()
+    .context_radius(3)
+    .header(&name, &name)
+    .to_string();
+  Ok(Some(diff))
+}
+
+/// 列出将被覆盖、且内容有实质变化的已有文件
+pub fn generate(args: &TaskArgs) -> Result<Vec<FileDiff>> {
+  let mut result = vec![];
+  for (path, content) in generated_files(args)? {
+    if !path.exists() {
+      trace!("{:?} 不存在，无需比较。", path);
+      continue;
+    }
+    match compare(&path, &content)? {
+      Some(diff) => result.push(FileDiff {
+        path: path.to_string(),
+        diff: diff,
+      }),
+      None => debug!("{:?} 与将生成的内容一致。", path),
+    }
+  }
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_normalize_text() {
+    assert_eq!(
+      normalize_text("  a   b \n\n\tc\r\n"),
+      vec!["a b".to_string(), "c".to_string()]
+    );
+    assert_eq!(normalize_text("a b"), normalize_text("a\tb\n\n"));
+    assert_ne!(normalize_text("ab"), normalize_text("a b"));
+  }
+
+  #[test]
+  fn test_compare_ignores_whitespace() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("settings.json");
+    let new = serde_json::json!({ "a": 1, "b": [true] });
+
+    fs::write(&path, "{ \"b\": [ true ], \"a\": 1 }").unwrap();
+    assert_eq!(compare(&path, &Content::Json(new.clone())).unwrap(), None);
+
+    // 含语法错误时退回文本比较，差异中不含空白的变化
+    let text = serde_json::to_string_pretty(&new).unwrap();
+    let broken = text.replace("  ", "\t").replace('}', "}}");
+    fs::write(&path, &broken).unwrap();
+    let diff = compare(&path, &Content::Json(new)).unwrap().unwrap();
+    let changed: Vec<_> = diff
+      .lines()
+      .filter(|l| l.starts_with(['+', '-']) && !l.starts_with("+++") && !l.starts_with("---"))
+      .collect();
+    assert_eq!(changed, vec!["-}}", "+}"]);
+  }
+
+  #[test]
+  fn test_compare_missing_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(".gitignore");
+    let diff = compare(&path, &Content::Text("/build/\n".to_string())).unwrap();
+    assert!(diff.unwrap().contains("+/build/"));
+    assert_eq!(
+      compare(&path, &Content::Text("\n".to_string())).unwrap(),
+      None
+    );
+  }
+}
@@ -16,48 +16,212 @@
 // along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
 use log::debug;
 use serde_json::json;
 
-use super::run;
-use super::TaskArgs;
+use super::{cmake, debugger, extension, gdb, makefile, modules, pkgconfig, remote, run};
+use super::{write_file, Content, TaskArgs};
+use crate::runtime::autolib;
 use crate::steps::compiler::CompilerType;
+use crate::steps::options::{BuildMode, BuildVariant, Debugger, IntelliSense, RunLocation};
 #[cfg(target_os = "macos")]
 use crate::utils::sysctl;
-use crate::utils::ToString;
+use crate::utils::{terminal, ToString};
 
 pub static EXT: &str = if cfg!(windows) { "exe" } else { "out" };
 pub static PATH_SLASH: &str = if cfg!(windows) { "\\" } else { "/" };
 pub static PATH_SEPARATOR: &str = if cfg!(windows) { ";" } else { ":" };
 
-fn single_file_build_task(args: &TaskArgs) -> Result<serde_json::Value> {
-  let debug = if args.setup.is_msvc() { "/Zi" } else { "-g" };
-  let output = if args.setup.is_msvc() { "/Fe:" } else { "-o" };
-  let mut c_args = vec![
-    debug.to_string(),
-    "${file}".to_string(),
-    output.to_string(),
-    format!(
-      "${{fileDirname}}{}${{fileBasenameNoExtension}}.{}",
-      PATH_SLASH, EXT
-    ),
-  ];
+/// 多文件构建时可执行文件的固定名称
+pub static PROJECT_PROGRAM: &str = "main";
+
+/// 运行时的工作目录。未指定输出文件夹时，可执行文件亦位于此处
+pub fn program_dir(args: &TaskArgs) -> &'static str {
+  match args.build_mode {
+    BuildMode::Workspace | BuildMode::CMake | BuildMode::Make => "${workspaceFolder}",
+    _ => "${fileDirname}",
+  }
+}
+
+/// 构建任务是否将可执行文件写入单独的输出文件夹。CMake 自行管理输出
+pub fn uses_output_dir(args: &TaskArgs) -> bool {
+  !args.output_dir.is_empty() && args.build_mode != BuildMode::CMake
+}
+
+/// 可执行文件所在的文件夹。按源文件所在的相对路径分开，避免不同文件夹中的同名源文件相互覆盖
+pub fn output_dir(args: &TaskArgs) -> String {
+  if !uses_output_dir(args) {
+    return program_dir(args).to_string();
+  }
+  match args.build_mode {
+    BuildMode::Workspace | BuildMode::Make => args.output_dir.clone(),
+    _ => format!("{}{}${{relativeFileDirname}}", args.output_dir, PATH_SLASH),
+  }
+}
+
+/// 发布构建的可执行文件名后缀，使两种构建的产物互不覆盖
+pub static RELEASE_SUFFIX: &str = "-release";
+
+/// 构建任务生成的可执行文件路径
+pub fn program_path(args: &TaskArgs, variant: BuildVariant) -> String {
+  if args.build_mode == BuildMode::CMake {
+    // 输出位置由 CMake 决定，交给 CMake Tools 解析
+    return "${command:cmake.launchTargetPath}".to_string();
+  }
+  let name = match args.build_mode {
+    BuildMode::Single => "${fileBasenameNoExtension}",
+    _ => PROJECT_PROGRAM,
+  };
+  let suffix = match variant {
+    BuildVariant::Debug => "",
+    BuildVariant::Release => RELEASE_SUFFIX,
+  };
+  format!(
+    "{}{}{}{}.{}",
+    output_dir(args),
+    PATH_SLASH,
+    name,
+    suffix,
+    EXT
+  )
+}
+
+pub fn build_task_label(args: &TaskArgs, variant: BuildVariant) -> &'static str {
+  if args.build_mode == BuildMode::CMake {
+    return cmake::BUILD_TASK_LABEL;
+  }
+  match variant {
+    BuildVariant::Debug => "debug build",
+    BuildVariant::Release => "release build",
+  }
+}
+
+/// 生成构建任务的构建配置。CMake 的构建类型由 CMake Tools 管理
+fn variants(args: &TaskArgs) -> &'static [BuildVariant] {
+  if args.build_mode == BuildMode::CMake {
+    &[BuildVariant::Debug]
+  } else {
+    &[BuildVariant::Debug, BuildVariant::Release]
+  }
+}
+
+fn variant_args(args: &TaskArgs, variant: BuildVariant) -> &'static [&'static str] {
+  match (args.setup.is_msvc(), variant) {
+    (false, BuildVariant::Debug) => &["-O0", "-g"],
+    (false, BuildVariant::Release) => &["-O2", "-DNDEBUG"],
+    (true, BuildVariant::Debug) => &["/Od", "/Zi"],
+    (true, BuildVariant::Release) => &["/O2", "/DNDEBUG"],
+  }
+}
+
+pub fn source_exts(args: &TaskArgs) -> &'static [&'static str] {
+  if args.is_c {
+    &["c"]
+  } else {
+    &["cpp", "cc", "cxx"]
+  }
+}
+
+/// 编译器命令。MSVC 依赖 vcvars64.bat 设置的环境，直接调用 cl.exe
+fn compiler_command(args: &TaskArgs) -> String {
+  if args.setup.is_msvc() {
+    "cl.exe".to_string()
+  } else {
+    args.compiler_path.to_string()
+  }
+}
+
+/// 除源文件与输出外的全部编译选项
+fn compile_flags(args: &TaskArgs, variant: BuildVariant) -> Vec<String> {
+  // 用户自行指定的优化选项位于其后，会覆盖这里的默认值
+  let mut flags: Vec<String> = variant_args(args, variant)
+    .iter()
+    .map(|a| a.to_string())
+    .collect();
   if args.setup.is_msvc() {
-    c_args.push("/EHsc".to_string());
+    flags.push("/EHsc".to_string());
     if !args
       .args
       .iter()
       .any(|a| a.starts_with("/execution-charset"))
     {
-      c_args.push("/execution-charset:utf-8".to_string());
+      flags.push("/execution-charset:utf-8".to_string());
     }
-    c_args.push("/source-charset:utf-8".to_string());
+    flags.push("/source-charset:utf-8".to_string());
   }
-  c_args.extend(args.args.clone());
-  let task_args = if args.setup.is_msvc() {
+  flags.extend(args.args.clone());
+  flags
+}
+
+/// vscch build 与 vscch judge 根据头文件追加选项的参数
+fn auto_lib_args(args: &TaskArgs) -> Result<Vec<String>> {
+  let mut result = vec![];
+  if !args.auto_libs {
+    return Ok(result);
+  }
+  result.push("--auto-libs".to_string());
+  for rule in &args.lib_rules {
+    autolib::parse_rule(rule)?;
+    result.push("--lib-rule".to_string());
+    result.push(rule.clone());
+  }
+  Ok(result)
+}
+
+/// 预编译模块与预编译头文件的存放位置，位于输出文件夹中
+fn cache_dir(args: &TaskArgs, name: &str) -> String {
+  let dir = if args.output_dir.is_empty() {
+    format!("${{workspaceFolder}}{}build", PATH_SLASH)
+  } else {
+    args.output_dir.clone()
+  };
+  format!("{}{}{}", dir, PATH_SLASH, name)
+}
+
+/// vscch build 预编译模块的参数。仅工作区构建时查找整个工作区中的模块单元，
+/// 否则只查找源文件所在的文件夹，以免编译无关的模块单元
+fn module_args(args: &TaskArgs) -> Vec<String> {
+  let mut result = vec![];
+  if !args.modules {
+    return result;
+  }
+  let module_dir = match args.build_mode {
+    BuildMode::Workspace => "${workspaceFolder}",
+    _ => "${fileDirname}",
+  };
+  result.extend([
+    "--modules".to_string(),
+    modules::compiler_kind(args.setup).to_string(),
+    "--module-dir".to_string(),
+    module_dir.to_string(),
+    "--module-cache".to_string(),
+    cache_dir(args, "modules"),
+  ]);
+  if let Some(std_module) = &args.std_module {
+    result.push("--std-module".to_string());
+    result.push(std_module.to_string());
+  }
+  result
+}
+
+/// vscch build 预编译头文件的参数
+fn pch_args(args: &TaskArgs) -> Vec<String> {
+  match &args.pch {
+    Some(header) => vec![
+      "--pch".to_string(),
+      header.clone(),
+      "--pch-cache".to_string(),
+      cache_dir(args, "pch"),
+    ],
+    None => vec![],
+  }
+}
+
+fn task_args(args: &TaskArgs, c_args: Vec<String>) -> serde_json::Value {
+  if args.setup.is_msvc() {
     // 参见 vcvars64.bat 附近的注释
     // quoting 设置为 escape 对于 cmd.exe 没有效果
     // https://github.com/microsoft/vscode/blob/0f9d0328ebe1eccd28e4de11ece14f4b0db3e818/src/vs/workbench/contrib/tasks/browser/terminalTaskSystem.ts#L1507
@@ -71,21 +235,83 @@ fn single_file_build_task(args: &TaskArgs) -> Result<serde_json::Value> {
     json!(args.collect::<Vec<_>>())
   } else {
     json!(c_args)
+  }
+}
+
+/// 构建是否需要经由 vscch build：多文件构建须在构建时收集源文件，
+/// 指令注释、按头文件链接库、模块与预编译头文件须在构建时读取源文件
+fn uses_wrapper(args: &TaskArgs) -> bool {
+  args.build_mode != BuildMode::Single
+    || args.directives
+    || args.auto_libs
+    || args.modules
+    || args.pch.is_some()
+}
+
+static MKDIR_TASK_LABEL: &str = "create output folder";
+
+/// 直接调用编译器时，在构建前创建输出文件夹的任务
+fn mkdir_task(args: &TaskArgs) -> serde_json::Value {
+  let dir = output_dir(args);
+  let (command, shell) = if cfg!(windows) {
+    // cmd.exe 的 mkdir 会一并创建中间的文件夹，但文件夹已存在时报错
+    (
+      format!("if not exist \"{0}\" mkdir \"{0}\"", dir),
+      json!({
+        "executable": "C:\\Windows\\System32\\cmd.exe",
+        "args": ["/d", "/c"]
+      }),
+    )
+  } else {
+    (
+      format!("mkdir -p '{}'", dir.replace('\'', "'\\''")),
+      json!({
+        "executable": "/bin/sh",
+        "args": ["-c"]
+      }),
+    )
   };
+  json!({
+    "type": "shell",
+    "label": MKDIR_TASK_LABEL,
+    "command": command,
+    "options": {
+      "shell": shell
+    },
+    "presentation": {
+      "reveal": "silent",
+      "focus": false,
+      "echo": false,
+      "showReuseMessage": false,
+      "panel": "shared",
+      "clear": true
+    },
+    "problemMatcher": []
+  })
+}
 
-  let mut compiler_cmd = args.compiler_path.to_string();
-  if args.setup.is_msvc() {
-    compiler_cmd = "cl.exe".to_string();
+fn build_task(args: &TaskArgs, variant: BuildVariant) -> Result<serde_json::Value> {
+  if args.build_mode == BuildMode::CMake {
+    return Ok(cmake::build_task(args));
+  }
+  if args.build_mode == BuildMode::Make {
+    return makefile::build_task(args, variant);
+  }
+  let output = if args.setup.is_msvc() { "/Fe:" } else { "-o" };
+  let mut c_args = vec![];
+  if args.build_mode == BuildMode::Single {
+    c_args.push("${file}".to_string());
   }
+  c_args.push(output.to_string());
+  c_args.push(program_path(args, variant));
+  c_args.extend(compile_flags(args, variant));
 
-  Ok(json!({
+  let mut task = json!({
     "type": if args.setup.is_msvc() { "shell" } else { "process" },
-    "label": "single file build",
-    "command": compiler_cmd,
-    "args": task_args,
+    "label": build_task_label(args, variant),
     "group": {
       "kind": "build",
-      "isDefault": true
+      "isDefault": variant == BuildVariant::Debug
     },
     "presentation": {
       "reveal": "silent",
@@ -96,25 +322,162 @@ fn single_file_build_task(args: &TaskArgs) -> Result<serde_json::Value> {
       "clear": true
     },
     "problemMatcher": if args.setup.is_msvc() { "$msCompile" } else { "$gcc" }
+  });
+  if !uses_wrapper(args) {
+    task["command"] = json!(compiler_command(args));
+    task["args"] = task_args(args, c_args);
+    if uses_output_dir(args) {
+      task["dependsOn"] = json!(MKDIR_TASK_LABEL);
+    }
+    return Ok(task);
+  }
+
+  // 源文件列表在构建时由 vscch build 收集，并插入到编译器之后；输出文件夹亦由其创建。
+  // 源文件开头指令注释中的选项也由其读取并追加
+  let mut wrapper_args = vec!["build".to_string()];
+  if uses_output_dir(args) {
+    wrapper_args.push("--out-dir".to_string());
+    wrapper_args.push(output_dir(args));
+  }
+  if args.directives {
+    wrapper_args.push("--directives".to_string());
+  }
+  wrapper_args.extend(auto_lib_args(args)?);
+  wrapper_args.extend(module_args(args));
+  wrapper_args.extend(pch_args(args));
+  if args.build_mode == BuildMode::Single {
+    wrapper_args.push("--scan".to_string());
+    wrapper_args.push("${file}".to_string());
+  } else {
+    for ext in source_exts(args) {
+      wrapper_args.push("--ext".to_string());
+      wrapper_args.push(ext.to_string());
+    }
+    for pattern in &args.exclude {
+      wrapper_args.push("--exclude".to_string());
+      wrapper_args.push(pattern.clone());
+    }
+    if args.build_mode == BuildMode::Workspace {
+      wrapper_args.push("--recursive".to_string());
+    }
+    wrapper_args.push(program_dir(args).to_string());
+  }
+  wrapper_args.push("--".to_string());
+  wrapper_args.push(compiler_command(args));
+  wrapper_args.extend(c_args);
+  task["command"] = json!(run::self_path()?.to_string());
+  task["args"] = task_args(args, wrapper_args);
+  Ok(task)
+}
+
+/// 以发布构建的选项编译当前文件，并用 vscch judge 逐个运行测试数据
+fn judge_task(args: &TaskArgs) -> Result<serde_json::Value> {
+  let mut c_args = vec!["judge".to_string(), "${file}".to_string()];
+  if args.directives {
+    c_args.push("--directives".to_string());
+  }
+  c_args.extend(auto_lib_args(args)?);
+  c_args.push("--".to_string());
+  c_args.push(compiler_command(args));
+  c_args.extend(compile_flags(args, BuildVariant::Release));
+  Ok(json!({
+    "type": if args.setup.is_msvc() { "shell" } else { "process" },
+    "label": "judge",
+    "command": run::self_path()?.to_string(),
+    "args": task_args(args, c_args),
+    "options": {
+      "cwd": "${fileDirname}"
+    },
+    "group": "test",
+    "presentation": {
+      "reveal": "always",
+      "focus": true,
+      "echo": false,
+      "showReuseMessage": false,
+      "panel": "dedicated",
+      "clear": true
+    },
+    "problemMatcher": if args.setup.is_msvc() { "$msCompile" } else { "$gcc" }
   }))
 }
 
-fn pause_task(args: &TaskArgs) -> Result<serde_json::Value> {
+/// 与源文件同名的输入文件
+static INPUT_FILE: &str = "${fileDirname}${pathSeparator}${fileBasenameNoExtension}.in";
+/// 与源文件同名的答案文件
+static ANSWER_FILE: &str = "${fileDirname}${pathSeparator}${fileBasenameNoExtension}.ans";
+
+/// 运行任务：由 vscch run 在新的终端窗口或任务终端中运行程序并暂停；
+/// 使用输出面板时直接运行程序
+fn run_task(args: &TaskArgs) -> Result<serde_json::Value> {
   let mut env = json!({});
   if args.setup.is_mingw() {
     let path = args.compiler_path.parent().unwrap().to_string();
     env = json!({ "Path": format!("{}{}${{env:Path}}", path, PATH_SEPARATOR) });
   }
+  let self_path = run::self_path()?.to_string();
+  let mut command = vec![];
+  if args.run_location != RunLocation::Internal {
+    command.extend([self_path.clone(), "run".to_string()]);
+    if args.run_location == RunLocation::Integrated {
+      command.push("--here".to_string());
+    }
+    command.extend(["--cwd".to_string(), program_dir(args).to_string()]);
+  }
+  if args.io_files {
+    // 输入、答案文件是否存在须在运行时判断，交给 vscch io 处理
+    command.extend([
+      self_path.clone(),
+      "io".to_string(),
+      "--input".to_string(),
+      INPUT_FILE.to_string(),
+      "--answer".to_string(),
+      ANSWER_FILE.to_string(),
+      "--".to_string(),
+    ]);
+  }
+  command.push(program_path(args, args.run_variant));
+  let (program, run_args) = command.split_first().unwrap();
   Ok(json!({
-    "type": "pause-console",
+    "type": "process",
     "label": "run and pause",
-    "command": format!("${{fileDirname}}{}${{fileBasenameNoExtension}}.{}", PATH_SLASH, EXT),
-    "dependsOn": "single file build",
-    "args": [],
+    "command": program,
+    "dependsOn": build_task_label(args, args.run_variant),
+    "args": run_args,
     "options": {
       "env": env,
-      "cwd": "${fileDirname}"
+      "cwd": program_dir(args)
+    },
+    "presentation": {
+      "reveal": if args.run_location == RunLocation::External { "silent" } else { "always" },
+      // 集成终端中的程序可能等待输入
+      "focus": args.run_location == RunLocation::Integrated,
+      "echo": false,
+      "showReuseMessage": false,
+      "panel": "shared",
+      "clear": true
     },
+    "problemMatcher": []
+  }))
+}
+
+/// 调试前的最后一个任务。调试器无法判断重定向的输入文件是否存在，故启用输入文件时先行创建
+fn pre_launch_task(args: &TaskArgs) -> &'static str {
+  if args.io_files {
+    "prepare input"
+  } else if args.ascii_check {
+    "ascii check"
+  } else {
+    build_task_label(args, BuildVariant::Debug)
+  }
+}
+
+fn prepare_input_task(args: &TaskArgs) -> Result<serde_json::Value> {
+  Ok(json!({
+    "type": "process",
+    "label": "prepare input",
+    "command": run::self_path()?.to_string(),
+    "dependsOn": if args.ascii_check { "ascii check" } else { build_task_label(args, BuildVariant::Debug) },
+    "args": ["io", "--input", INPUT_FILE, "--touch"],
     "presentation": {
       "reveal": "never",
       "focus": false,
@@ -127,19 +490,19 @@ fn pause_task(args: &TaskArgs) -> Result<serde_json::Value> {
   }))
 }
 
-fn ascii_check_task(_: &TaskArgs) -> Result<serde_json::Value> {
+fn ascii_check_task(args: &TaskArgs) -> Result<serde_json::Value> {
   Ok(json!({
     "type": "process",
     "label": "ascii check",
     "command": "C:\\Windows\\system32\\WindowsPowerShell\\v1.0\\powershell.exe",
-    "dependsOn": "single file build",
+    "dependsOn": build_task_label(args, BuildVariant::Debug),
     "args": [
       "-ExecutionPolicy",
       "ByPass",
       "-NoProfile",
       "-File",
       run::checker_path()?.to_string(),
-      "${fileDirname}\\${fileBasenameNoExtension}.exe"
+      program_path(args, BuildVariant::Debug)
     ],
     "presentation": {
       "reveal": "never",
@@ -153,14 +516,27 @@ fn ascii_check_task(_: &TaskArgs) -> Result<serde_json::Value> {
   }))
 }
 
-pub fn tasks_json(args: &TaskArgs) -> Result<()> {
-  let mut task_list = vec![single_file_build_task(args)?];
-  if !args.compatible_mode {
-    task_list.push(pause_task(args)?);
+fn tasks_json_content(args: &TaskArgs) -> Result<serde_json::Value> {
+  let mut task_list = variants(args)
+    .iter()
+    .map(|v| build_task(args, *v))
+    .collect::<Result<Vec<_>>>()?;
+  if !uses_wrapper(args) && uses_output_dir(args) {
+    task_list.push(mkdir_task(args));
   }
+  task_list.push(run_task(args)?);
   if args.ascii_check {
     task_list.push(ascii_check_task(args)?);
   }
+  if args.io_files {
+    task_list.push(prepare_input_task(args)?);
+  }
+  if args.build_mode == BuildMode::Single {
+    task_list.push(judge_task(args)?);
+  }
+  if let Some(remote) = &args.remote {
+    task_list.push(remote::gdbserver_task(args, remote)?);
+  }
   let mut options = json!({});
 
   if cfg!(windows) {
@@ -203,103 +579,204 @@ pub fn tasks_json(args: &TaskArgs) -> Result<()> {
     }
   }
 
-  let json = json!({
+  Ok(json!({
     "version": "2.0.0",
     "tasks": task_list,
     "options": options
-  });
+  }))
+}
 
+pub fn tasks_json(args: &TaskArgs) -> Result<()> {
+  let json = tasks_json_content(args)?;
   debug!("tasks.json: {}", json);
-
-  let path = args.workspace.join(".vscode").join("tasks.json");
-  fs::write(path, serde_json::to_string_pretty(&json)?)?;
-  Ok(())
+  write_file(
+    args,
+    dotvscode_path(args, "tasks.json"),
+    Content::Json(json),
+  )
 }
 
-pub fn launch_json(args: &TaskArgs) -> Result<()> {
-  let debugger_name: String = match args.setup.ty {
-    CompilerType::GCC => "gdb",
-    CompilerType::LLVM => "lldb",
-    CompilerType::MSVC => "",
-  }
-  .into();
-
-  let debugger_ext = if cfg!(windows) { ".exe" } else { "" };
+/// cppdbg 各调试配置共用的 GDB 设置
+fn mi_settings(args: &TaskArgs) -> serde_json::Map<String, serde_json::Value> {
+  let mut settings = serde_json::Map::new();
+  settings.insert("MIMode".into(), json!("gdb"));
+  settings.insert(
+    "miDebuggerPath".into(),
+    json!(debugger::mi_debugger_path(args)),
+  );
+  settings.insert("setupCommands".into(), json!(gdb::setup_commands(args)));
+  settings
+}
 
-  let debug_type = match args.setup.ty {
-    CompilerType::GCC => "cppdbg",
-    CompilerType::LLVM => "lldb",
-    CompilerType::MSVC => "cppvsdbg",
+/// 调试 core 文件时询问其路径。Linux 的默认 core_pattern 将其写入程序的工作目录，macOS 则写入 /cores
+fn core_file_input(args: &TaskArgs) -> serde_json::Value {
+  let default = if cfg!(windows) {
+    format!("{}{}", program_dir(args), PATH_SLASH)
+  } else if cfg!(target_os = "macos") {
+    "/cores/".to_string()
+  } else {
+    format!("{}{}core", program_dir(args), PATH_SLASH)
   };
+  json!({
+    "id": "coreFile",
+    "type": "promptString",
+    "description": if cfg!(windows) { "转储文件（.dmp）路径" } else { "core 文件路径" },
+    "default": default
+  })
+}
 
+fn launch_json_content(args: &TaskArgs) -> Result<serde_json::Value> {
+  let debug_type = debugger::debug_type(args);
   let bin_path = args.compiler_path.parent().unwrap();
-  let debugger_path = bin_path
-    .join(format!("{}{}", debugger_name, debugger_ext))
-    .to_string();
-  let console_settings = if debug_type == "cppdbg" {
-    (
-      "externalConsole",
-      serde_json::to_value(!args.compatible_mode)?,
-    )
+  let path_value = format!("{}{}${{env:PATH}}", bin_path.to_string(), PATH_SEPARATOR);
+  let program = program_path(args, BuildVariant::Debug);
+  let mi_settings = if debug_type == "cppdbg" || args.remote.is_some() {
+    mi_settings(args)
   } else {
-    ("console", serde_json::to_value("externalTerminal")?)
+    serde_json::Map::new()
   };
 
-  let mut json = json!({
-    "version": "0.2.0",
-    "configurations": [
-      {
-        "name": "single file debug",
-        "type": debug_type,
-        "request": "launch",
-        "program": format!("${{fileDirname}}{}${{fileBasenameNoExtension}}.{}", PATH_SLASH, EXT),
-        "args": [],
-        "stopAtEntry": false,
-        "cwd": "${fileDirname}",
-        "environment": [
-          {
-            "name": "PATH",
-            "value": format!("{}{}${{env:PATH}}", bin_path.to_string(), PATH_SEPARATOR)
-          }
-        ],
-        console_settings.0: console_settings.1,
-        "MIMode": debugger_name,          // Only used in cppdbg (GDB mode)
-        "miDebuggerPath": debugger_path,  // ..
-        "preLaunchTask": if args.ascii_check { "ascii check" } else { "single file build" },
-        "internalConsoleOptions": "neverOpen"
-      }
-    ]
+  let mut launch = json!({
+    "name": "single file debug",
+    "type": debug_type,
+    "request": "launch",
+    "program": program,
+    "args": [],
+    "cwd": program_dir(args),
+    "preLaunchTask": pre_launch_task(args),
+    "internalConsoleOptions": if args.run_location == RunLocation::Internal { "openOnSessionStart" } else { "neverOpen" }
   });
-  if debug_type == "cppdbg" {
-    json["configurations"][0]["setupCommands"] = json!([
-      {
-        "description": "Enable pretty-printing for gdb",
-        "text": "-enable-pretty-printing",
-        "ignoreFailures": true
-      }
-    ]);
+  let config = launch.as_object_mut().unwrap();
+  match debug_type {
+    "cppdbg" => {
+      config.insert("stopAtEntry".into(), json!(false));
+      config.insert(
+        "environment".into(),
+        json!([{ "name": "PATH", "value": path_value }]),
+      );
+      // cppdbg 只能选择是否使用外部终端
+      config.insert(
+        "externalConsole".into(),
+        json!(args.run_location == RunLocation::External),
+      );
+      config.extend(mi_settings.clone());
+    }
+    "lldb-dap" => {
+      config.insert("stopOnEntry".into(), json!(false));
+      config.insert("env".into(), json!([format!("PATH={}", path_value)]));
+      // lldb-dap 不支持外部终端，只能在集成终端与调试控制台之间选择
+      config.insert(
+        "runInTerminal".into(),
+        json!(args.run_location != RunLocation::Internal),
+      );
+    }
+    _ => {
+      config.insert("stopAtEntry".into(), json!(false));
+      config.insert(
+        "environment".into(),
+        json!([{ "name": "PATH", "value": path_value }]),
+      );
+      config.insert("console".into(), json!(args.run_location.console()));
+    }
+  }
+  if args.io_files {
+    if debug_type == "lldb" || debug_type == "lldb-dap" {
+      config.insert("stdio".into(), json!([INPUT_FILE, null, null]));
+    } else {
+      // cppdbg 与 cppvsdbg 均支持在 args 中使用重定向
+      config.insert("args".into(), json!(["<", INPUT_FILE]));
+    }
   }
 
-  debug!("launch.json: {}", json);
+  // 附加到正在运行的程序
+  let mut attach = json!({
+    "name": "attach to process",
+    "type": debug_type,
+    "request": "attach",
+    "program": program
+  });
+  let config = attach.as_object_mut().unwrap();
+  match debug_type {
+    "cppdbg" => {
+      config.insert("processId".into(), json!("${command:pickProcess}"));
+      config.extend(mi_settings.clone());
+    }
+    "cppvsdbg" => {
+      config.remove("program");
+      config.insert("processId".into(), json!("${command:pickProcess}"));
+    }
+    _ => {
+      config.insert("pid".into(), json!("${command:pickProcess}"));
+    }
+  }
 
-  let path = args.workspace.join(".vscode").join("launch.json");
-  fs::write(path, serde_json::to_string_pretty(&json)?)?;
-  Ok(())
+  // 程序崩溃后检查其 core 文件（Windows 下为转储文件）
+  let mut core = json!({
+    "name": "debug core dump",
+    "type": debug_type,
+    "request": "launch",
+    "program": program,
+    "cwd": program_dir(args)
+  });
+  let config = core.as_object_mut().unwrap();
+  match debug_type {
+    "cppdbg" => {
+      config.insert("coreDumpPath".into(), json!("${input:coreFile}"));
+      config.extend(mi_settings.clone());
+    }
+    "cppvsdbg" => {
+      config.insert("dumpPath".into(), json!("${input:coreFile}"));
+    }
+    "lldb" => {
+      // CodeLLDB 通过自定义的 target create 命令加载 core 文件
+      config.insert("request".into(), json!("custom"));
+      config.remove("program");
+      config.insert(
+        "targetCreateCommands".into(),
+        json!([format!(
+          "target create -c \"${{input:coreFile}}\" \"{}\"",
+          program
+        )]),
+      );
+      config.insert("processCreateCommands".into(), json!([]));
+    }
+    _ => {
+      config.insert("request".into(), json!("attach"));
+      config.insert("coreFile".into(), json!("${input:coreFile}"));
+    }
+  }
+
+  let mut configurations = vec![launch, attach, core];
+  if let Some(remote) = &args.remote {
+    configurations.push(remote::launch_config(args, remote, mi_settings)?);
+  }
+
+  Ok(json!({
+    "version": "0.2.0",
+    "configurations": configurations,
+    "inputs": [core_file_input(args)]
+  }))
 }
 
-pub fn c_cpp_properties_json(args: &TaskArgs) -> Result<()> {
+pub fn launch_json(args: &TaskArgs) -> Result<()> {
+  #[cfg(target_os = "linux")]
+  debugger::check_core_dump();
+  let json = launch_json_content(args)?;
+  debug!("launch.json: {}", json);
+  write_file(
+    args,
+    dotvscode_path(args, "launch.json"),
+    Content::Json(json),
+  )
+}
+
+fn intellisense_mode(args: &TaskArgs) -> Result<String> {
   let im_compiler = match args.setup.ty {
     CompilerType::GCC => "gcc",
     CompilerType::LLVM => "clang",
     CompilerType::MSVC => "msvc",
   };
   let im_platform = std::env::consts::OS;
-  let name = match im_platform {
-    "windows" => "Win32",
-    "macos" => "Mac",
-    "linux" => "Linux",
-    _ => return Err(anyhow!("unknown platform")),
-  };
 
   #[cfg(not(target_os = "macos"))]
   let im_arch = "x64";
@@ -310,32 +787,221 @@ pub fn c_cpp_properties_json(args: &TaskArgs) -> Result<()> {
     sysctl::X64 => "x64",
   };
 
+  Ok(format!("{}-{}-{}", im_platform, im_compiler, im_arch))
+}
+
+/// cppStandard 与 cStandard 接受的取值。C/C++ 扩展尚不接受 c++26，以 c++23 代替
+fn intellisense_standard(args: &TaskArgs) -> String {
+  let standard = match args.standard.as_str() {
+    "c++26" => "c++23",
+    s => s,
+  };
+  if args.use_gnu {
+    format!("gnu{}", &standard[1..])
+  } else {
+    standard.to_string()
+  }
+}
+
+fn c_cpp_properties_json_content(args: &TaskArgs) -> Result<serde_json::Value> {
+  let name = match std::env::consts::OS {
+    "windows" => "Win32",
+    "macos" => "Mac",
+    "linux" => "Linux",
+    _ => return Err(anyhow!("unknown platform")),
+  };
+
   let standard_key = if args.is_c {
     "cStandard"
   } else {
     "cppStandard"
   };
 
-  let json = json!({
+  let mut include_path = vec!["${workspaceFolder}/**".to_string()];
+  include_path.extend(args.include_dirs.iter().cloned());
+  include_path.extend(pkgconfig::include_dirs(args));
+  let mut json = json!({
     "version": 4i32,
     "configurations": [
       {
         "name": name,
-        "includePath": [
-          "${{workspaceFolder}}/**"
-        ],
+        "includePath": include_path,
         "compilerPath": args.compiler_path.to_string(),
-        standard_key: args.standard.to_ascii_lowercase(),
-        "intelliSenseMode": format!("{}-{}-{}", im_platform, im_compiler, im_arch),
+        standard_key: intellisense_standard(args),
+        "intelliSenseMode": intellisense_mode(args)?,
       }
     ]
   });
+  if !args.defines.is_empty() {
+    json["configurations"][0]["defines"] = json!(args.defines);
+  }
+  if args.build_mode == BuildMode::CMake {
+    json["configurations"][0]["configurationProvider"] = json!(extension::CMAKE_TOOLS_ID);
+  }
+  Ok(json)
+}
 
+pub fn c_cpp_properties_json(args: &TaskArgs) -> Result<()> {
+  let json = c_cpp_properties_json_content(args)?;
   debug!("c_cpp_properties.json: {}", json);
+  write_file(
+    args,
+    dotvscode_path(args, "c_cpp_properties.json"),
+    Content::Json(json),
+  )
+}
 
-  let path = args.workspace.join(".vscode").join("c_cpp_properties.json");
-  fs::write(path, serde_json::to_string_pretty(&json)?)?;
-  Ok(())
+/// 是否需要生成工作区设置。clangd 与 lldb-dap 总是需要
+pub fn writes_settings(args: &TaskArgs) -> bool {
+  args.workspace_settings
+    || args.modules
+    || args.intellisense == IntelliSense::Clangd
+    || debugger::debugger(args) == Some(Debugger::LldbDap)
+}
+
+/// 将本工具管理的设置项合并到已有的 settings.json 中，其余设置项保持不变
+fn settings_json_content(args: &TaskArgs) -> Result<serde_json::Value> {
+  let path = dotvscode_path(args, "settings.json");
+  let mut json = if path.exists() {
+    let content = fs::read_to_string(&path)?;
+    // JSON5 是 vscode JSON with comment 的超集。直接用 serde_json 可能出错
+    json5::from_str(&content)?
+  } else {
+    json!({})
+  };
+  let settings = json
+    .as_object_mut()
+    .ok_or(anyhow!("settings.json 的内容应为 object 类型。"))?;
+  if args.workspace_settings {
+    settings.insert("files.encoding".into(), json!("utf8"));
+    // 以便正确打开 GBK 等编码的旧文件
+    settings.insert("files.autoGuessEncoding".into(), json!(true));
+    // 构建失败时不再启动调试，避免运行旧的可执行文件
+    settings.insert("debug.onTaskErrors".into(), json!("abort"));
+    if args.intellisense == IntelliSense::CppTools {
+      let standard_key = if args.is_c {
+        "C_Cpp.default.cStandard"
+      } else {
+        "C_Cpp.default.cppStandard"
+      };
+      settings.insert(
+        "C_Cpp.default.compilerPath".into(),
+        json!(args.compiler_path.to_string()),
+      );
+      settings.insert(standard_key.into(), json!(intellisense_standard(args)));
+      settings.insert(
+        "C_Cpp.default.intelliSenseMode".into(),
+        json!(intellisense_mode(args)?),
+      );
+    }
+    if !args.setup.is_msvc() {
+      // 使集成终端中能直接调用编译器和运行程序
+      let (platform, path_key) = match std::env::consts::OS {
+        "windows" => ("windows", "Path"),
+        "macos" => ("osx", "PATH"),
+        _ => ("linux", "PATH"),
+      };
+      let bin_path = args.compiler_path.parent().unwrap().to_string();
+      let env_key = format!("terminal.integrated.env.{}", platform);
+      let mut env = settings
+        .get(&env_key)
+        .and_then(|e| e.as_object())
+        .cloned()
+        .unwrap_or_default();
+      env.insert(
+        path_key.into(),
+        json!(format!(
+          "{}{}${{env:{}}}",
+          bin_path, PATH_SEPARATOR, path_key
+        )),
+      );
+      settings.insert(env_key, json!(env));
+    }
+    if cfg!(target_os = "linux") && args.run_location == RunLocation::External {
+      // 否则 VS Code 与调试器只会尝试少数几种终端
+      if let Some(terminal) = terminal::detect() {
+        settings.insert(
+          "terminal.external.linuxExec".into(),
+          json!(terminal.path.to_string()),
+        );
+      }
+    }
+  }
+  if args.modules {
+    // 使模块单元作为 C++ 源文件打开
+    let mut associations = settings
+      .get("files.associations")
+      .and_then(|a| a.as_object())
+      .cloned()
+      .unwrap_or_default();
+    for ext in modules::UNIT_EXTS {
+      associations.insert(format!("*.{}", ext), json!("cpp"));
+    }
+    settings.insert("files.associations".into(), json!(associations));
+  }
+  if debugger::debugger(args) == Some(Debugger::LldbDap) {
+    // lldb-dap 扩展不自带调试适配器
+    settings.insert(
+      "lldb-dap.executable-path".into(),
+      json!(debugger::lldb_dap_path(args).to_string()),
+    );
+  }
+  if args.intellisense == IntelliSense::Clangd {
+    // 避免 C/C++ 扩展与 clangd 同时提供补全和诊断
+    settings.insert("C_Cpp.intelliSenseEngine".into(), json!("disabled"));
+    let mut clangd_args = vec![];
+    if !args.setup.is_msvc() {
+      clangd_args.push(format!("--query-driver={}", args.compiler_path.to_string()));
+    }
+    if args.modules {
+      clangd_args.push("--experimental-modules-support".to_string());
+    }
+    if !clangd_args.is_empty() {
+      settings.insert("clangd.arguments".into(), json!(clangd_args));
+    }
+  }
+  Ok(json)
+}
+
+pub fn settings_json(args: &TaskArgs) -> Result<()> {
+  let json = settings_json_content(args)?;
+  debug!("settings.json: {}", json);
+  write_file(
+    args,
+    dotvscode_path(args, "settings.json"),
+    Content::Json(json),
+  )
+}
+
+fn dotvscode_path(args: &TaskArgs, name: &str) -> PathBuf {
+  args.workspace.join(".vscode").join(name)
+}
+
+/// .vscode 文件夹下将要生成的全部文件
+pub fn files(args: &TaskArgs) -> Result<Vec<(PathBuf, Content)>> {
+  let mut files = vec![
+    (
+      dotvscode_path(args, "tasks.json"),
+      Content::Json(tasks_json_content(args)?),
+    ),
+    (
+      dotvscode_path(args, "launch.json"),
+      Content::Json(launch_json_content(args)?),
+    ),
+  ];
+  if args.intellisense == IntelliSense::CppTools {
+    files.push((
+      dotvscode_path(args, "c_cpp_properties.json"),
+      Content::Json(c_cpp_properties_json_content(args)?),
+    ));
+  }
+  if writes_settings(args) {
+    files.push((
+      dotvscode_path(args, "settings.json"),
+      Content::Json(settings_json_content(args)?),
+    ));
+  }
+  Ok(files)
 }
 
 pub fn create_folder(args: &TaskArgs) -> Result<()> {
@@ -26,7 +26,8 @@ use std::path::PathBuf;
 use std::process::Command;
 use std::sync::Mutex;
 
-use super::TaskArgs;
+use super::{debugger, TaskArgs};
+use crate::steps::options::{BuildMode, Debugger, IntelliSense};
 #[cfg(windows)]
 use crate::utils::winapi::CREATE_NO_WINDOW;
 
@@ -121,11 +122,48 @@ impl ExtensionManager {
 
 static C_CPP_ID: &str = "ms-vscode.cpptools";
 static CODE_LLDB_ID: &str = "vadimcn.vscode-lldb";
-static PAUSER_ID: &str = "Guyutongxue.pause-console";
+static LLDB_DAP_ID: &str = "llvm-vs-code-extensions.lldb-dap";
+static CLANGD_ID: &str = "llvm-vs-code-extensions.vscode-clangd";
+pub static CMAKE_TOOLS_ID: &str = "ms-vscode.cmake-tools";
+static UNRECOMMENDED_IDS: &[&str] = &[
+  "formulahendry.code-runner",
+  "austin.code-gnu-global",
+  "danielpinto8zz6.c-cpp-compile-run",
+  "mitaki28.vscode-clang",
+  "jaycetyle.vscode-gnu-global",
+  "franneck94.c-cpp-runner",
+  "ajshort.include-autocomplete",
+  "xaver.clang-format",
+  "jbenden.c-cpp-flylint",
+];
+
+/// 按当前配置应当安装的扩展
+pub fn required(args: &TaskArgs) -> Vec<&'static str> {
+  let mut ids = vec![C_CPP_ID];
+  if let Some(id) = debugger_id(args) {
+    ids.push(id);
+  }
+  if args.intellisense == IntelliSense::Clangd {
+    ids.push(CLANGD_ID);
+  }
+  if args.build_mode == BuildMode::CMake {
+    ids.push(CMAKE_TOOLS_ID);
+  }
+  ids
+}
 
-pub fn install_pauser(args: &TaskArgs) -> Result<()> {
-  let mut m = ExtensionManager::get(args).lock().unwrap();
-  m.install(PAUSER_ID)
+/// 按当前配置应当卸载的扩展
+pub fn unrecommended(args: &TaskArgs) -> Vec<&'static str> {
+  if args.remove_extensions {
+    UNRECOMMENDED_IDS.to_vec()
+  } else {
+    vec![]
+  }
+}
+
+pub fn installed(args: &TaskArgs) -> Vec<String> {
+  let m = ExtensionManager::get(args).lock().unwrap();
+  m.installed.clone()
 }
 
 pub fn install_c_cpp(args: &TaskArgs) -> Result<()> {
@@ -133,25 +171,31 @@ pub fn install_c_cpp(args: &TaskArgs) -> Result<()> {
   m.install(C_CPP_ID)
 }
 
-pub fn install_code_lldb(args: &TaskArgs) -> Result<()> {
+/// 调试器扩展。GDB 与 MSVC 的调试由 C/C++ 扩展提供
+pub fn debugger_id(args: &TaskArgs) -> Option<&'static str> {
+  match debugger::debugger(args) {
+    Some(Debugger::CodeLldb) => Some(CODE_LLDB_ID),
+    Some(Debugger::LldbDap) => Some(LLDB_DAP_ID),
+    _ => None,
+  }
+}
+
+pub fn install_debugger(args: &TaskArgs) -> Result<()> {
+  let mut m = ExtensionManager::get(args).lock().unwrap();
+  m.install(debugger_id(args).unwrap())
+}
+
+pub fn install_clangd(args: &TaskArgs) -> Result<()> {
+  let mut m = ExtensionManager::get(args).lock().unwrap();
+  m.install(CLANGD_ID)
+}
+
+pub fn install_cmake_tools(args: &TaskArgs) -> Result<()> {
   let mut m = ExtensionManager::get(args).lock().unwrap();
-  m.install(CODE_LLDB_ID)
+  m.install(CMAKE_TOOLS_ID)
 }
 
 pub fn remove_unrecommended(args: &TaskArgs) -> Result<()> {
   let mut m = ExtensionManager::get(args).lock().unwrap();
-  [
-    "formulahendry.code-runner",
-    "austin.code-gnu-global",
-    "danielpinto8zz6.c-cpp-compile-run",
-    "mitaki28.vscode-clang",
-    "jaycetyle.vscode-gnu-global",
-    "franneck94.c-cpp-runner",
-    "ajshort.include-autocomplete",
-    "xaver.clang-format",
-    "jbenden.c-cpp-flylint",
-  ]
-  .iter()
-  .map(|id| m.uninstall(id))
-  .collect()
+  UNRECOMMENDED_IDS.iter().map(|id| m.uninstall(id)).collect()
 }
@@ -0,0 +1,175 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use log::{debug, warn};
+use serde_json::json;
+
+use super::TaskArgs;
+#[cfg(windows)]
+use crate::utils::winapi::CREATE_NO_WINDOW;
+use crate::utils::ToString;
+
+fn run_compiler(args: &TaskArgs, compiler_args: &[&str]) -> Option<Output> {
+  let mut command = Command::new(&args.compiler_path);
+  #[cfg(windows)]
+  command.creation_flags(CREATE_NO_WINDOW);
+  command
+    .args(compiler_args)
+    .stdin(Stdio::null())
+    .output()
+    .ok()
+}
+
+/// libstdc++ 的 Python 美化输出脚本所在的文件夹（其中含有 libstdcxx 包）
+///
+/// 脚本安装在编译器前缀下的 share/gcc-<版本>/python 或 share/gcc/python 中。
+/// 前缀由 libstdc++ 库所在位置向上查找；自行编译或解压的 GCC 则从编译器位置向上查找。
+fn printers_dir(args: &TaskArgs) -> Option<PathBuf> {
+  let mut bases = vec![];
+  if let Some(output) = run_compiler(args, &["-print-file-name=libstdc++.so"]) {
+    let lib = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    // 找不到时 GCC 原样输出文件名
+    if lib.is_absolute() {
+      bases.push(lib);
+    }
+  }
+  bases.push(args.compiler_path.clone());
+  for base in &bases {
+    for prefix in base.ancestors().skip(1) {
+      let pattern = format!(
+        "{}/share/gcc*/python",
+        glob::Pattern::escape(&prefix.to_string())
+      );
+      let found = glob::glob(&pattern)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find(|p| p.join("libstdcxx").is_dir());
+      if found.is_some() {
+        return found;
+      }
+    }
+  }
+  None
+}
+
+/// 去除路径中的 . 与 ..，不访问文件系统
+fn lexical_normalize(path: &Path) -> PathBuf {
+  let mut result = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir => {
+        result.pop();
+      }
+      c => result.push(c),
+    }
+  }
+  result
+}
+
+/// libstdc++ 头文件所在的文件夹，取自编译器的头文件搜索路径
+fn std_include_dirs(args: &TaskArgs) -> Vec<PathBuf> {
+  let output = match run_compiler(args, &["-xc++", "-E", "-v", "-"]) {
+    Some(output) => output,
+    None => return vec![],
+  };
+  let mut dirs = vec![];
+  for line in String::from_utf8_lossy(&output.stderr)
+    .lines()
+    .skip_while(|l| !l.starts_with("#include <...>"))
+    .skip(1)
+    .take_while(|l| !l.starts_with("End of search list"))
+  {
+    let dir = Path::new(line.trim()).to_path_buf();
+    if !dir.components().any(|c| c.as_os_str() == "c++") {
+      continue;
+    }
+    // 调试信息中的路径可能保留 ..，也可能已被化简，两者都需跳过
+    let normalized = lexical_normalize(&dir);
+    if normalized != dir {
+      dirs.push(normalized);
+    }
+    dirs.push(dir);
+  }
+  // 如 c++/12/backward 已被 c++/12 的规则覆盖
+  dirs
+    .iter()
+    .filter(|d| !dirs.iter().any(|o| o != *d && d.starts_with(o)))
+    .cloned()
+    .collect()
+}
+
+/// cppdbg 在启动 GDB 后执行的命令
+pub fn setup_commands(args: &TaskArgs) -> Vec<serde_json::Value> {
+  let mut commands = vec![json!({
+    "description": "Enable pretty-printing for gdb",
+    "text": "-enable-pretty-printing",
+    "ignoreFailures": true
+  })];
+  if args.gdb_print_object {
+    commands.push(json!({
+      "description": "Show dynamic type of polymorphic objects",
+      "text": "set print object on",
+      "ignoreFailures": true
+    }));
+    commands.push(json!({
+      "description": "Hide static members",
+      "text": "set print static-members off",
+      "ignoreFailures": true
+    }));
+  }
+  if args.is_c {
+    return commands;
+  }
+  if args.gdb_pretty_printers {
+    match printers_dir(args) {
+      Some(dir) => {
+        debug!("libstdc++ 美化输出脚本位于 {:?}", dir);
+        // 系统 GDB 可能已自动加载同一脚本，此时重复注册会失败，忽略即可
+        commands.push(json!({
+          "description": "Load libstdc++ pretty printers",
+          "text": format!(
+            "python import sys; sys.path.insert(0, r'{}'); from libstdcxx.v6.printers import register_libstdcxx_printers; register_libstdcxx_printers(None)",
+            dir.to_string()
+          ),
+          "ignoreFailures": true
+        }));
+      }
+      None => warn!("找不到 libstdc++ 的美化输出脚本，调试时 STL 容器将显示为原始结构。"),
+    }
+  }
+  if args.gdb_skip_std {
+    // skip -gfi 的通配符不匹配路径分隔符，故逐层列出
+    for dir in std_include_dirs(args) {
+      let dir = dir.to_string().replace('\\', "/");
+      for pattern in ["*", "*/*", "*/*/*"] {
+        commands.push(json!({
+          "description": "Skip standard library headers",
+          "text": format!("skip -gfi {}/{}", dir, pattern),
+          "ignoreFailures": true
+        }));
+      }
+    }
+  }
+  commands
+}
@@ -0,0 +1,124 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::{debug, warn};
+
+use super::dotvscode::{uses_output_dir, EXT, PROJECT_PROGRAM, RELEASE_SUFFIX};
+use super::{makefile, write_file, Content, TaskArgs};
+use crate::steps::compiler::CompilerType;
+use crate::steps::options::BuildMode;
+
+static HEADER: &str = "# 构建输出（由 VSCodeConfigHelper 添加）";
+
+fn path(args: &TaskArgs) -> PathBuf {
+  args.workspace.join(".gitignore")
+}
+
+/// 构建输出对应的忽略规则
+fn patterns(args: &TaskArgs) -> Vec<String> {
+  let mut patterns = vec![];
+  match args.build_mode {
+    BuildMode::CMake => patterns.push("/build/".to_string()),
+    BuildMode::Make => match makefile::relative_output_dir(args) {
+      Some(relative) if relative.is_empty() => {
+        patterns.push("/build/".to_string());
+        patterns.push(format!("/{}.{}", PROJECT_PROGRAM, EXT));
+        patterns.push(format!("/{}{}.{}", PROJECT_PROGRAM, RELEASE_SUFFIX, EXT));
+      }
+      Some(relative) => patterns.push(format!("/{}/", relative)),
+      None => debug!("输出文件夹 {} 不在工作区中，无需忽略。", args.output_dir),
+    },
+    _ if uses_output_dir(args) => match args.output_dir.strip_prefix("${workspaceFolder}") {
+      Some(relative) => {
+        let relative = relative.replace('\\', "/");
+        let relative = relative.trim_matches('/');
+        if relative.is_empty() {
+          warn!("输出文件夹即工作区文件夹，.gitignore 中将忽略全部可执行文件。");
+          patterns.push(format!("*.{}", EXT));
+        } else {
+          patterns.push(format!("/{}/", relative));
+        }
+      }
+      None => debug!("输出文件夹 {} 不在工作区中，无需忽略。", args.output_dir),
+    },
+    _ => patterns.push(format!("*.{}", EXT)),
+  }
+  if args.output_dir.is_empty() {
+    if args.modules {
+      patterns.push("/build/modules/".to_string());
+    }
+    if args.pch.is_some() {
+      patterns.push("/build/pch/".to_string());
+    }
+  }
+  if args.modules && args.setup.ty == CompilerType::GCC {
+    // GCC 总是将预编译模块写入工作目录下的 gcm.cache
+    patterns.push("/gcm.cache/".to_string());
+  }
+  if args.setup.is_msvc() {
+    // cl.exe 在工作目录中留下的中间文件与调试信息
+    patterns.extend(["*.obj", "*.pdb", "*.ilk"].iter().map(|p| p.to_string()));
+  }
+  patterns
+}
+
+/// 在已有的 .gitignore 末尾追加缺少的规则，其余内容保持不变
+fn content(args: &TaskArgs) -> Result<String> {
+  let path = path(args);
+  let mut content = if path.exists() {
+    fs::read_to_string(&path)?
+  } else {
+    String::new()
+  };
+  let existing: Vec<String> = content.lines().map(|l| l.trim().to_string()).collect();
+  let missing: Vec<String> = patterns(args)
+    .into_iter()
+    .filter(|p| !existing.contains(p))
+    .collect();
+  if missing.is_empty() {
+    return Ok(content);
+  }
+  if !content.is_empty() && !content.ends_with('\n') {
+    content.push('\n');
+  }
+  if !existing.iter().any(|l| l == HEADER) {
+    if !content.is_empty() {
+      content.push('\n');
+    }
+    content.push_str(HEADER);
+    content.push('\n');
+  }
+  for pattern in missing {
+    content.push_str(&pattern);
+    content.push('\n');
+  }
+  Ok(content)
+}
+
+pub fn files(args: &TaskArgs) -> Result<Vec<(PathBuf, Content)>> {
+  Ok(vec![(path(args), Content::Text(content(args)?))])
+}
+
+pub fn gitignore(args: &TaskArgs) -> Result<()> {
+  let content = content(args)?;
+  debug!(".gitignore: {}", content);
+  write_file(args, path(args), Content::Text(content))
+}
@@ -0,0 +1,283 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde_json::json;
+
+use super::dotvscode::{build_task_label, source_exts, EXT, PROJECT_PROGRAM, RELEASE_SUFFIX};
+use super::{is_link_arg, write_file, Content, TaskArgs};
+use crate::steps::options::BuildVariant;
+use crate::utils::ToString;
+
+/// 用于识别由本工具生成的 Makefile；不含此标记的文件视为用户自己的项目
+static GENERATED_MARK: &str = "# Generated by VSCodeConfigHelper";
+
+pub fn path(args: &TaskArgs) -> PathBuf {
+  args.workspace.join("Makefile")
+}
+
+/// 工作区中是否已有用户自己编写的 Makefile
+pub fn user_makefile_exists(args: &TaskArgs) -> bool {
+  match fs::read_to_string(path(args)) {
+    Ok(content) => !content.starts_with(GENERATED_MARK),
+    Err(_) => false,
+  }
+}
+
+fn check_supported(args: &TaskArgs) -> Result<()> {
+  if args.setup.is_msvc() {
+    return Err(anyhow!(
+      "MSVC 不支持 Makefile 构建方式，请改用 CMake 或多文件构建。"
+    ));
+  }
+  Ok(())
+}
+
+/// make 程序。MinGW 发行版通常将其命名为 mingw32-make.exe 并置于编译器旁
+fn make_command(args: &TaskArgs) -> String {
+  if args.setup.is_mingw() {
+    let mingw_make = args.compiler_path.with_file_name("mingw32-make.exe");
+    if mingw_make.exists() {
+      return mingw_make.to_string();
+    }
+  }
+  "make".to_string()
+}
+
+/// make 对路径中的空格不友好，带空格的编译器路径需加引号
+fn quote(s: &str) -> String {
+  if s.contains(' ') {
+    format!("\"{}\"", s)
+  } else {
+    s.to_string()
+  }
+}
+
+fn mkdir_command() -> &'static str {
+  if cfg!(windows) {
+    "@if not exist \"$(subst /,\\,$(@D))\" mkdir \"$(subst /,\\,$(@D))\""
+  } else {
+    "@mkdir -p $(@D)"
+  }
+}
+
+/// 输出文件夹相对于工作区的路径，供 Makefile 直接使用。未指定输出文件夹时为空字符串；
+/// 不在工作区中时为 None
+pub fn relative_output_dir(args: &TaskArgs) -> Option<String> {
+  if args.output_dir.is_empty() {
+    return Some(String::new());
+  }
+  let relative = args.output_dir.strip_prefix("${workspaceFolder}")?;
+  Some(relative.replace('\\', "/").trim_matches('/').to_string())
+}
+
+/// 将排除规则转换为 make 的 filter-out 模式，匹配以 ./ 开头的源文件路径。
+/// $(wildcard) 不支持 **，故将 ** 连同其两侧的 / 转换为 %（匹配任意多级文件夹）；
+/// 其余部分含通配符时无法如此转换，仍交给 $(wildcard) 逐级匹配
+fn exclude_filters(pattern: &str) -> Vec<String> {
+  let pattern = pattern.trim_start_matches("./");
+  let parts: Vec<_> = pattern.split("**").collect();
+  if parts.len() == 1 {
+    return vec![format!("$(wildcard ./{})", pattern)];
+  }
+  if parts.len() > 2 || parts.iter().any(|p| p.contains(['*', '?'])) {
+    warn!(
+      "Makefile 不支持排除规则 {}，其中的 ** 将只匹配一级文件夹。",
+      pattern
+    );
+    return vec![format!("$(wildcard ./{})", pattern.replace("**", "*"))];
+  }
+  let (prefix, suffix) = (parts[0], parts[1]);
+  let mut filters = vec![format!("./{}%{}", prefix, suffix)];
+  // dir/**/name 与 **/name 还匹配中间没有文件夹的情形
+  if (prefix.is_empty() || prefix.ends_with('/')) && suffix.starts_with('/') {
+    filters.push(format!("./{}{}", prefix, &suffix[1..]));
+  }
+  filters
+}
+
+fn content(args: &TaskArgs) -> String {
+  let (cc, cflags) = if args.is_c {
+    ("CC", "CFLAGS")
+  } else {
+    ("CXX", "CXXFLAGS")
+  };
+  let (link_args, compile_args): (Vec<_>, Vec<_>) =
+    args.args.iter().cloned().partition(|a| is_link_arg(a));
+  let patterns = source_exts(args)
+    .iter()
+    .map(|ext| format!("*.{}", ext))
+    .collect::<Vec<_>>()
+    .join(" ");
+  let exclude = args
+    .exclude
+    .iter()
+    .flat_map(|p| exclude_filters(p))
+    .collect::<Vec<_>>()
+    .join(" ");
+  // 输出文件夹不在工作区中时，由构建任务在命令行上以 VS Code 变量指定
+  let (out_dir, build_dir, target_prefix) = match relative_output_dir(args) {
+    Some(relative) if relative.is_empty() => (".".to_string(), "build", ""),
+    Some(relative) => (relative, "$(OUT_DIR)", "$(OUT_DIR)/"),
+    None => ("build".to_string(), "$(OUT_DIR)", "$(OUT_DIR)/"),
+  };
+
+  let lines = vec![
+    GENERATED_MARK.to_string(),
+    "# 删除第一行后，本工具将不再覆盖此文件。".to_string(),
+    "".to_string(),
+    format!("{} := {}", cc, quote(&args.compiler_path.to_string())),
+    format!("{} := {}", cflags, compile_args.join(" ")),
+    format!("LDLIBS := {}", link_args.join(" ")),
+    "".to_string(),
+    format!("OUT_DIR := {}", out_dir),
+    format!("BUILD_DIR := {}", build_dir),
+    format!("TARGET := {}{}.{}", target_prefix, PROJECT_PROGRAM, EXT),
+    format!(
+      "RELEASE_TARGET := {}{}{}.{}",
+      target_prefix, PROJECT_PROGRAM, RELEASE_SUFFIX, EXT
+    ),
+    "".to_string(),
+    "# 递归查找源文件，跳过构建目录".to_string(),
+    "rwildcard = $(foreach d,$(wildcard $(1:=/*)),$(call rwildcard,$d,$2) $(filter $(subst *,%,$2),$d))".to_string(),
+    format!("SRCS := $(call rwildcard,.,{})", patterns),
+    "SRCS := $(filter-out ./$(BUILD_DIR)/%,$(SRCS))".to_string(),
+    format!("SRCS := $(filter-out {},$(SRCS))", exclude),
+    "SRCS := $(patsubst ./%,%,$(SRCS))".to_string(),
+    "".to_string(),
+    "DEBUG_OBJS := $(SRCS:%=$(BUILD_DIR)/debug/%.o)".to_string(),
+    "RELEASE_OBJS := $(SRCS:%=$(BUILD_DIR)/release/%.o)".to_string(),
+    "".to_string(),
+    ".PHONY: all debug release clean".to_string(),
+    "all: debug".to_string(),
+    "debug: $(TARGET)".to_string(),
+    "release: $(RELEASE_TARGET)".to_string(),
+    "".to_string(),
+    "$(TARGET): $(DEBUG_OBJS)".to_string(),
+    format!("\t{}", mkdir_command()),
+    format!("\t$({}) $^ -o $@ $(LDLIBS)", cc),
+    "".to_string(),
+    "$(RELEASE_TARGET): $(RELEASE_OBJS)".to_string(),
+    format!("\t{}", mkdir_command()),
+    format!("\t$({}) $^ -o $@ $(LDLIBS)", cc),
+    "".to_string(),
+    "$(BUILD_DIR)/debug/%.o: %".to_string(),
+    format!("\t{}", mkdir_command()),
+    format!("\t$({}) $({}) -O0 -g -MMD -MP -c $< -o $@", cc, cflags),
+    "".to_string(),
+    "$(BUILD_DIR)/release/%.o: %".to_string(),
+    format!("\t{}", mkdir_command()),
+    format!(
+      "\t$({}) $({}) -O2 -DNDEBUG -MMD -MP -c $< -o $@",
+      cc, cflags
+    ),
+    "".to_string(),
+    "clean:".to_string(),
+    if cfg!(windows) {
+      "\t@if exist $(BUILD_DIR) rmdir /s /q $(BUILD_DIR)\n\t@if exist $(TARGET) del /q $(TARGET)\n\t@if exist $(RELEASE_TARGET) del /q $(RELEASE_TARGET)"
+        .to_string()
+    } else {
+      "\trm -rf $(BUILD_DIR) $(TARGET) $(RELEASE_TARGET)".to_string()
+    },
+    "".to_string(),
+    "-include $(DEBUG_OBJS:.o=.d) $(RELEASE_OBJS:.o=.d)".to_string(),
+    "".to_string(),
+  ];
+  lines.join("\n")
+}
+
+/// 调用 make 的构建任务；只重新编译有改动的源文件
+pub fn build_task(args: &TaskArgs, variant: BuildVariant) -> Result<serde_json::Value> {
+  check_supported(args)?;
+  let target = match variant {
+    BuildVariant::Debug => "debug",
+    BuildVariant::Release => "release",
+  };
+  let mut make_args = vec![target.to_string()];
+  if relative_output_dir(args).is_none() {
+    make_args.push(format!("OUT_DIR={}", args.output_dir));
+  }
+  Ok(json!({
+    "type": "process",
+    "label": build_task_label(args, variant),
+    "command": make_command(args),
+    "args": make_args,
+    "options": {
+      "cwd": "${workspaceFolder}"
+    },
+    "group": {
+      "kind": "build",
+      "isDefault": variant == BuildVariant::Debug
+    },
+    "presentation": {
+      "reveal": "silent",
+      "focus": false,
+      "echo": false,
+      "showReuseMessage": false,
+      "panel": "shared",
+      "clear": true
+    },
+    "problemMatcher": "$gcc"
+  }))
+}
+
+/// Makefile 构建方式下将要生成的文件。用户自己的 Makefile 不在其列
+pub fn files(args: &TaskArgs) -> Vec<(PathBuf, Content)> {
+  if user_makefile_exists(args) {
+    vec![]
+  } else {
+    vec![(path(args), Content::Text(content(args)))]
+  }
+}
+
+pub fn makefile(args: &TaskArgs) -> Result<()> {
+  check_supported(args)?;
+  if user_makefile_exists(args) {
+    warn!("工作区中已有 Makefile，将保留它。请确保其中定义了 debug 目标。");
+    return Ok(());
+  }
+  let content = content(args);
+  debug!("Makefile: {}", content);
+  write_file(args, path(args), Content::Text(content))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_exclude_filters() {
+    assert_eq!(
+      exclude_filters("test/*.cpp"),
+      vec!["$(wildcard ./test/*.cpp)"]
+    );
+    assert_eq!(exclude_filters("old/**"), vec!["./old/%"]);
+    assert_eq!(
+      exclude_filters("**/main.cpp"),
+      vec!["./%/main.cpp", "./main.cpp"]
+    );
+    assert_eq!(exclude_filters("./a/**/b.c"), vec!["./a/%/b.c", "./a/b.c"]);
+    assert_eq!(
+      exclude_filters("**/*_test.cpp"),
+      vec!["$(wildcard ./*/*_test.cpp)"]
+    );
+  }
+}
@@ -20,26 +20,46 @@ use derivative::*;
 use log::{debug, info, trace, warn};
 use serde::Deserialize;
 
+use std::fs;
 use std::path::PathBuf;
 use std::{path::Path, sync::Arc};
 
+use crate::steps::compiler::Compiler;
 use crate::steps::compiler::{stdchoose, CompilerSetup, CompilerType};
+use crate::steps::options::{
+  BuildMode, BuildVariant, Debugger, DiagnosticsColor, IntelliSense, Options, RemoteDebug,
+  RunLocation, WarningLevel,
+};
 use crate::steps::vscode::adjust_path as adjust_vscode;
-use crate::steps::{compiler::Compiler, options::Options};
 use crate::utils::ToString;
 
+pub mod check;
+pub mod clangd;
+pub mod cmake;
+pub mod debugger;
+pub mod diff;
 pub mod dotvscode;
 pub mod extension;
+pub mod gdb;
+pub mod gitignore;
+pub mod makefile;
+pub mod modules;
+pub mod pkgconfig;
+pub mod remote;
 pub mod run;
 pub mod statistics;
 pub mod test;
 
 #[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct TaskInitArgs {
   pub vscode: String,
   pub compiler: Compiler,
   pub workspace: String,
   pub options: Options,
+  /// 用户拒绝覆盖的文件
+  #[serde(default)]
+  pub skipped_files: Vec<String>,
 }
 
 #[derive(Derivative)]
@@ -49,13 +69,44 @@ pub struct TaskArgs {
   #[derivative(Debug = "ignore")]
   pub setup: &'static CompilerSetup,
   pub compiler_path: PathBuf,
+  pub compiler_version: String,
   pub workspace: PathBuf,
   pub run_hotkey: String,
-  pub compatible_mode: bool,
+  pub run_location: RunLocation,
   pub is_c: bool,
   pub file_ext: &'static str,
+  /// 规范的标准名称，如 c++20；GNU 方言由 use_gnu 表示
   pub standard: String,
+  pub use_gnu: bool,
   pub args: Vec<String>,
+  /// 以下两项已包含在 args 中，另供 IntelliSense 配置使用
+  pub include_dirs: Vec<String>,
+  pub defines: Vec<String>,
+  pub modules: bool,
+  /// std 模块的源文件；为 None 时不支持 import std
+  pub std_module: Option<PathBuf>,
+  pub module_error: Option<String>,
+  pub pch: Option<String>,
+  pub build_mode: BuildMode,
+  pub run_variant: BuildVariant,
+  pub output_dir: String,
+  pub gitignore: bool,
+  pub exclude: Vec<String>,
+  pub directives: bool,
+  pub auto_libs: bool,
+  pub lib_rules: Vec<String>,
+  pub libraries: Vec<String>,
+  /// 由 pkg-config 查询到的选项，已包含在 args 中
+  pub pkg_flags: Vec<String>,
+  pub pkg_error: Option<String>,
+  pub intellisense: IntelliSense,
+  pub workspace_settings: bool,
+  pub io_files: bool,
+  pub debugger: Debugger,
+  pub gdb_pretty_printers: bool,
+  pub gdb_skip_std: bool,
+  pub gdb_print_object: bool,
+  pub remote: Option<RemoteDebug>,
   pub ascii_check: bool,
   pub remove_extensions: bool,
   pub add_to_path: bool,
@@ -63,6 +114,174 @@ pub struct TaskArgs {
   pub test_file: Option<String>,
   pub desktop_shortcut: bool,
   pub collect_data: bool,
+  pub skipped_files: Vec<PathBuf>,
+}
+
+/// 任务将要写入的文件内容
+pub enum Content {
+  Json(serde_json::Value),
+  Text(String),
+}
+
+impl Content {
+  pub fn to_text(&self) -> Result<String> {
+    match self {
+      Content::Json(json) => Ok(serde_json::to_string_pretty(json)?),
+      Content::Text(text) => Ok(text.clone()),
+    }
+  }
+}
+
+/// 是否为链接阶段的选项，生成项目构建文件时需与编译选项分开
+pub fn is_link_arg(arg: &str) -> bool {
+  arg.starts_with("-l")
+    || arg.starts_with("-L")
+    || arg.starts_with("/link")
+    || arg.to_ascii_uppercase().starts_with("/LIBPATH:")
+    || arg.to_ascii_lowercase().ends_with(".lib")
+}
+
+/// MSVC 标准遵循选项及最早支持它的 Visual Studio 版本。旧版本的 cl.exe 不认识较新的选项（D9002）
+static MSVC_CONFORMANCE: &[(&str, &str)] = &[
+  ("/permissive-", "15.5"),
+  ("/Zc:__cplusplus", "15.7"),
+  ("/Zc:__STDC__", "17.2"),
+  ("/Zc:enumTypes", "17.4"),
+  ("/Zc:externConstexpr", "15.6"),
+  ("/Zc:lambda", "16.8"),
+  ("/Zc:preprocessor", "16.5"),
+  ("/Zc:referenceBinding", "15.0"),
+  ("/Zc:rvalueCast", "15.0"),
+  ("/Zc:strictStrings", "15.0"),
+  ("/Zc:templateScope", "17.5"),
+  ("/Zc:ternary", "15.8"),
+  ("/Zc:throwingNew", "15.0"),
+];
+
+/// 所给 Visual Studio 版本支持的标准遵循选项。版本无法识别时视为最新版本
+fn msvc_conformance_args(version: &str) -> Vec<&'static str> {
+  MSVC_CONFORMANCE
+    .iter()
+    .filter(|(_, since)| {
+      version.is_empty() || version_compare::compare(version, since) != Ok(version_compare::Cmp::Lt)
+    })
+    .map(|(arg, _)| *arg)
+    .collect()
+}
+
+/// 警告、标准遵循、执行字符集与诊断颜色对应的编译选项。位于用户指定的选项之前，以便被其覆盖。
+/// version 为编译器版本，MSVC 则为 Visual Studio 的版本
+fn diagnostic_args(options: &Options, msvc: bool, version: &str) -> Vec<String> {
+  let mut result: Vec<&str> = vec![];
+  if options.pedantic {
+    if msvc {
+      result.extend(msvc_conformance_args(version));
+    } else {
+      result.push("-pedantic");
+    }
+  }
+  match (options.warning_level, msvc) {
+    (WarningLevel::None, false) => result.push("-w"),
+    (WarningLevel::None, true) => result.push("/W0"),
+    (WarningLevel::Default, _) => (),
+    (WarningLevel::Extra, false) => result.extend(["-Wall", "-Wextra"]),
+    (WarningLevel::Extra, true) => result.push("/W4"),
+    (WarningLevel::Strict, false) => result.extend(["-Wall", "-Wextra", "-Werror"]),
+    (WarningLevel::Strict, true) => result.extend(["/W4", "/WX"]),
+  }
+  if options.acp_output {
+    result.push(if msvc {
+      "/execution-charset:gbk"
+    } else {
+      "-fexec-charset=GBK"
+    });
+  }
+  // MSVC 的诊断信息没有颜色
+  if !msvc {
+    match options.diagnostics_color {
+      DiagnosticsColor::Auto => (),
+      DiagnosticsColor::Always => result.push("-fdiagnostics-color=always"),
+      DiagnosticsColor::Never => result.push("-fdiagnostics-color=never"),
+    }
+  }
+  result.into_iter().map(|a| a.to_string()).collect()
+}
+
+/// 将头文件路径、库路径、库与宏定义转换为对应编译器的选项
+fn structured_args(options: &Options, msvc: bool) -> Vec<String> {
+  let mut result = vec![];
+  if msvc {
+    result.extend(options.include_dirs.iter().map(|d| format!("/I{}", d)));
+    result.extend(options.defines.iter().map(|d| format!("/D{}", d)));
+    result.extend(options.lib_dirs.iter().map(|d| format!("/LIBPATH:{}", d)));
+    result.extend(options.libs.iter().map(|l| {
+      if l.to_ascii_lowercase().ends_with(".lib") {
+        l.clone()
+      } else {
+        format!("{}.lib", l)
+      }
+    }));
+  } else {
+    result.extend(options.include_dirs.iter().map(|d| format!("-I{}", d)));
+    result.extend(options.defines.iter().map(|d| format!("-D{}", d)));
+    result.extend(options.lib_dirs.iter().map(|d| format!("-L{}", d)));
+    result.extend(options.libs.iter().map(|l| format!("-l{}", l)));
+  }
+  result
+}
+
+/// 追加编译选项。MSVC 的 /LIBPATH 是链接器选项，须位于 /link 之后，其余选项则须位于其前
+fn extend_args(args: &mut Vec<String>, new_args: Vec<String>, msvc: bool) {
+  if !msvc {
+    args.extend(new_args);
+    return;
+  }
+  let (linker, compiler): (Vec<_>, Vec<_>) = new_args
+    .into_iter()
+    .partition(|a| a.to_ascii_uppercase().starts_with("/LIBPATH:"));
+  let position = args
+    .iter()
+    .position(|a| a.eq_ignore_ascii_case("/link"))
+    .unwrap_or(args.len());
+  args.splice(position..position, compiler);
+  if !linker.is_empty() {
+    if !args.iter().any(|a| a.eq_ignore_ascii_case("/link")) {
+      args.push("/link".to_string());
+    }
+    args.extend(linker);
+  }
+}
+
+/// 写入生成的文件；用户拒绝覆盖的文件会被跳过
+pub fn write_file(args: &TaskArgs, path: PathBuf, content: Content) -> Result<()> {
+  if args.skipped_files.contains(&path) {
+    info!("保留原有的 {:?}，不再写入。", path);
+    return Ok(());
+  }
+  fs::write(path, content.to_text()?)?;
+  Ok(())
+}
+
+/// 列出所有将要生成的配置文件及其内容，但不写入磁盘
+pub fn generated_files(args: &TaskArgs) -> Result<Vec<(PathBuf, Content)>> {
+  pkgconfig::check(args)?;
+  modules::check(args)?;
+  let mut files = dotvscode::files(args)?;
+  if args.intellisense == IntelliSense::Clangd {
+    files.extend(clangd::files(args));
+  }
+  if args.build_mode == BuildMode::CMake {
+    files.extend(cmake::files(args)?);
+  }
+  if args.build_mode == BuildMode::Make {
+    files.extend(makefile::files(args));
+  }
+  if args.gitignore {
+    files.extend(gitignore::files(args)?);
+  }
+  let (path, json) = run::keybinding_content(args)?;
+  files.push((path, Content::Json(json)));
+  Ok(files)
 }
 
 struct Task {
@@ -184,7 +403,7 @@ macro_rules! generate_task {
   };
 }
 
-pub fn list(mut args: TaskInitArgs) -> Vec<(&'static str, Box<dyn Fn() -> Result<()> + Send>)> {
+pub fn init(mut args: TaskInitArgs) -> TaskArgs {
   let is_c = args.options.language == "C";
   let file_ext = if is_c { "c" } else { "cpp" };
   let vscode = adjust_vscode(Path::new(&args.vscode));
@@ -213,40 +432,120 @@ pub fn list(mut args: TaskInitArgs) -> Vec<(&'static str, Box<dyn Fn() -> Result
     }
   };
   let setup = *args.compiler.setup;
-  let standard = args.options.standard.as_ref().cloned().unwrap_or_else(|| {
-    let std_p = match setup.ty {
-      CompilerType::GCC => stdchoose::gcc,
-      CompilerType::LLVM => stdchoose::clang,
-      CompilerType::MSVC => (|_| ("c++20", "c17")) as fn(&str) -> (&'static str, &'static str),
-    }(&args.compiler.version);
-    (if is_c { std_p.1 } else { std_p.0 }).into()
-  });
+  let version = &args.compiler.version;
+  let default_standard = stdchoose::default_standard(setup.ty, version, is_c);
+  let (standard, gnu) =
+    stdchoose::normalize(args.options.standard.as_deref().unwrap_or(default_standard));
+  let mut use_gnu = args.options.use_gnu || gnu;
+  if use_gnu && setup.is_msvc() {
+    warn!("MSVC 不支持 GNU 方言，将使用 {}。", standard);
+    use_gnu = false;
+  }
+  let (standard, std_name) =
+    choose_standard(setup.ty, version, standard, default_standard, use_gnu);
+  if args.options.acp_output && !cfg!(windows) {
+    warn!("以 GBK 编码输出仅适用于 Windows，已忽略。");
+    args.options.acp_output = false;
+  }
+  let diagnostics = diagnostic_args(&args.options, setup.is_msvc(), version);
+  args.options.args.splice(0..0, diagnostics);
   {
     let std_arg_prefix = if setup.is_msvc() { "/std:" } else { "-std=" };
-    if !args
-      .options
-      .args
-      .iter()
-      .any(|a| a.starts_with(std_arg_prefix))
-    {
-      let std_arg = format!("{}{}", std_arg_prefix, standard);
+    // MSVC 的 C89、C99 与 C++14 之前的标准没有对应的选项
+    if let Some(std_name) = std_name.filter(|_| {
+      !args
+        .options
+        .args
+        .iter()
+        .any(|a| a.starts_with(std_arg_prefix))
+    }) {
+      let std_arg = format!("{}{}", std_arg_prefix, std_name);
       info!("在编译选项中添加 {}。", std_arg);
       args.options.args.push(std_arg);
     }
   }
+  let (pkg_flags, pkg_error) = match pkgconfig::resolve(&args.options.libraries, setup.is_msvc()) {
+    Ok(flags) => (flags, None),
+    Err(e) => (vec![], Some(e.to_string())),
+  };
+  // 相对路径相对于工作区文件夹。CMake 等在其它文件夹中调用编译器，故转换为绝对路径
+  for dir in args
+    .options
+    .include_dirs
+    .iter_mut()
+    .chain(args.options.lib_dirs.iter_mut())
+  {
+    if !dir.starts_with('$') && Path::new(dir.as_str()).is_relative() {
+      *dir = workspace.join(&dir).to_string();
+    }
+  }
+  let compiler_path = (setup.path_to_exe)(&args.compiler.path, is_c);
+  let module_error = if args.options.modules {
+    modules::unsupported_reason(setup, version, is_c, &standard, args.options.build_mode)
+  } else {
+    None
+  };
+  let std_module = if args.options.modules && module_error.is_none() {
+    modules::std_module(setup, &compiler_path).map(|(path, flags)| {
+      args.options.args.extend(flags);
+      path
+    })
+  } else {
+    None
+  };
+  let mut pch = args.options.pch.clone().filter(|h| !h.is_empty());
+  if pch.is_some() && setup.is_msvc() {
+    warn!("MSVC 不支持以 -include 使用预编译头文件，将不使用预编译头文件。");
+    pch = None;
+  }
+  if pch.is_some() && [BuildMode::CMake, BuildMode::Make].contains(&args.options.build_mode) {
+    warn!("CMake 与 Makefile 构建方式不使用预编译头文件。");
+    pch = None;
+  }
+  let msvc = setup.is_msvc();
+  let structured = structured_args(&args.options, msvc);
+  extend_args(&mut args.options.args, structured, msvc);
+  extend_args(&mut args.options.args, pkg_flags.clone(), msvc);
   statistics::set(args.options.collect_data);
 
-  let args = Arc::from(TaskArgs {
+  TaskArgs {
     vscode,
     setup,
-    compiler_path: (setup.path_to_exe)(&args.compiler.path, is_c),
+    compiler_path: compiler_path,
+    compiler_version: args.compiler.version.clone(),
     workspace,
     run_hotkey: args.options.run_hotkey,
-    compatible_mode: args.options.compatible_mode,
+    run_location: args.options.run_location,
     is_c: is_c,
     file_ext: file_ext,
-    standard: standard.to_string(),
+    standard: standard,
+    use_gnu: use_gnu,
     args: args.options.args,
+    include_dirs: args.options.include_dirs,
+    defines: args.options.defines,
+    modules: args.options.modules,
+    std_module: std_module,
+    module_error: module_error,
+    pch: pch,
+    build_mode: args.options.build_mode,
+    run_variant: args.options.run_variant,
+    output_dir: args.options.output_dir,
+    gitignore: args.options.gitignore,
+    exclude: args.options.exclude,
+    directives: args.options.directives,
+    auto_libs: args.options.auto_libs,
+    lib_rules: args.options.lib_rules,
+    libraries: args.options.libraries,
+    pkg_flags: pkg_flags,
+    pkg_error: pkg_error,
+    intellisense: args.options.intellisense,
+    workspace_settings: args.options.workspace_settings,
+    io_files: args.options.io_files,
+    debugger: args.options.debugger,
+    gdb_pretty_printers: args.options.gdb_pretty_printers,
+    gdb_skip_std: args.options.gdb_skip_std,
+    gdb_print_object: args.options.gdb_print_object,
+    remote: args.options.remote,
     ascii_check: args.options.ascii_check,
     remove_extensions: args.options.remove_extensions,
     add_to_path: args.options.add_to_path,
@@ -254,7 +553,12 @@ pub fn list(mut args: TaskInitArgs) -> Vec<(&'static str, Box<dyn Fn() -> Result
     test_file: test_file,
     desktop_shortcut: args.options.desktop_shortcut,
     collect_data: args.options.collect_data,
-  });
+    skipped_files: args.skipped_files.iter().map(PathBuf::from).collect(),
+  }
+}
+
+pub fn list(args: TaskArgs) -> Vec<(&'static str, Box<dyn Fn() -> Result<()> + Send>)> {
+  let args = Arc::from(args);
 
   trace!("args passed to tasks: {:?}", args);
 
@@ -264,17 +568,28 @@ pub fn list(mut args: TaskInitArgs) -> Vec<(&'static str, Box<dyn Fn() -> Result
   };
 
   generate_task![
+    (pkgconfig::check, a => !a.libraries.is_empty()),
+    (modules::check, a => a.modules),
     (extension::remove_unrecommended, a => a.remove_extensions),
     (extension::install_c_cpp, _ => true),
-    (extension::install_code_lldb, a => a.setup.ty == CompilerType::LLVM),
-    (extension::install_pauser, a => !a.compatible_mode),
-    (run::create_keybinding, a => !a.compatible_mode),
+    (extension::install_debugger, a => extension::debugger_id(a).is_some()),
+    (extension::install_clangd, a => a.intellisense == IntelliSense::Clangd),
+    (extension::install_cmake_tools, a => a.build_mode == BuildMode::CMake),
+    (run::create_keybinding, _ => true),
     (debug::create_checker, a => a.ascii_check),
+    (run::install_self, _ => true),
     (compiler::add_to_path, a => a.setup.is_mingw() && a.add_to_path),
     (dotvscode::create_folder, _ => true),
     (dotvscode::tasks_json, _ => true),
     (dotvscode::launch_json, _ => true),
-    (dotvscode::c_cpp_properties_json, _ => true),
+    (dotvscode::c_cpp_properties_json, a => a.intellisense == IntelliSense::CppTools),
+    (dotvscode::settings_json, a => dotvscode::writes_settings(a)),
+    (clangd::compile_flags, a => a.intellisense == IntelliSense::Clangd),
+    (clangd::dot_clangd, a => a.intellisense == IntelliSense::Clangd),
+    (cmake::cmake_lists, a => a.build_mode == BuildMode::CMake),
+    (cmake::cmake_presets, a => a.build_mode == BuildMode::CMake),
+    (makefile::makefile, a => a.build_mode == BuildMode::Make),
+    (gitignore::gitignore, a => a.gitignore),
     (test::generate, a => a.test_file.is_some()),
     (shortcut::create, a => a.desktop_shortcut),
     (vscode::open, a => a.open_vscode),
@@ -285,3 +600,68 @@ pub fn list(mut args: TaskInitArgs) -> Vec<(&'static str, Box<dyn Fn() -> Result
   .map(mapper)
   .collect()
 }
+
+/// 选定的标准及其在编译器命令行中的名称。编译器过旧、不支持所选标准时改用默认标准；
+/// 编译器没有对应选项的标准则保留，不添加选项
+fn choose_standard(
+  ty: CompilerType,
+  version: &str,
+  standard: String,
+  default_standard: &str,
+  gnu: bool,
+) -> (String, Option<String>) {
+  let std_name = stdchoose::spelling(ty, version, &standard, gnu);
+  if std_name.is_some() || standard == default_standard || stdchoose::without_switch(ty, &standard)
+  {
+    return (standard, std_name);
+  }
+  warn!(
+    "编译器（版本 {}）不支持语言标准 {}，改用 {}。",
+    version, standard, default_standard
+  );
+  let std_name = stdchoose::spelling(ty, version, default_standard, gnu);
+  (default_standard.to_string(), std_name)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_msvc_conformance_args() {
+    let old = msvc_conformance_args("16.11.5");
+    assert!(old.contains(&"/permissive-"));
+    assert!(old.contains(&"/Zc:lambda"));
+    assert!(!old.contains(&"/Zc:__STDC__"));
+    assert!(!old.contains(&"/Zc:templateScope"));
+    let new = msvc_conformance_args("17.5.33424.131");
+    assert_eq!(new.len(), MSVC_CONFORMANCE.len());
+    assert_eq!(msvc_conformance_args("").len(), MSVC_CONFORMANCE.len());
+  }
+
+  #[test]
+  fn test_choose_standard() {
+    let msvc = |standard: &str| {
+      choose_standard(
+        CompilerType::MSVC,
+        "17.8.0",
+        standard.to_string(),
+        "c17",
+        false,
+      )
+    };
+    assert_eq!(msvc("c99"), ("c99".to_string(), None));
+    assert_eq!(msvc("c11"), ("c11".to_string(), Some("c11".to_string())));
+    assert_eq!(msvc("c23"), ("c17".to_string(), Some("c17".to_string())));
+    assert_eq!(
+      choose_standard(
+        CompilerType::GCC,
+        "12.2.0",
+        "c++26".to_string(),
+        "c++20",
+        true
+      ),
+      ("c++20".to_string(), Some("gnu++20".to_string()))
+    );
+  }
+}
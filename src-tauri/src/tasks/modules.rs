@@ -0,0 +1,148 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use version_compare::Version;
+
+use super::TaskArgs;
+use crate::steps::compiler::{CompilerSetup, CompilerType};
+use crate::steps::options::BuildMode;
+#[cfg(windows)]
+use crate::utils::winapi::CREATE_NO_WINDOW;
+use crate::utils::ToString;
+
+pub use crate::runtime::modules::UNIT_EXTS;
+
+/// 支持模块的最低版本。MSVC 以 Visual Studio 的版本区分
+fn minimum_version(ty: CompilerType) -> &'static str {
+  match ty {
+    CompilerType::GCC => "11.0",
+    CompilerType::LLVM => "16.0",
+    CompilerType::MSVC => "17.5",
+  }
+}
+
+/// 传给 vscch build 的编译器种类
+pub fn compiler_kind(setup: &CompilerSetup) -> &'static str {
+  match setup.ty {
+    CompilerType::GCC => "gcc",
+    CompilerType::LLVM => "clang",
+    CompilerType::MSVC => "msvc",
+  }
+}
+
+/// 当前的编译器与选项能否使用模块；不能时返回原因
+pub fn unsupported_reason(
+  setup: &CompilerSetup,
+  version: &str,
+  is_c: bool,
+  standard: &str,
+  build_mode: BuildMode,
+) -> Option<String> {
+  if is_c {
+    return Some("C 语言没有模块。".to_string());
+  }
+  if ![BuildMode::Single, BuildMode::Folder, BuildMode::Workspace].contains(&build_mode) {
+    return Some("模块仅支持单文件、文件夹与工作区构建模式。".to_string());
+  }
+  if ["c++98", "c++03", "c++11", "c++14", "c++17"].contains(&standard) {
+    return Some(format!(
+      "模块需要 C++20 或更新的标准，当前为 {}。",
+      standard
+    ));
+  }
+  let minimum = minimum_version(setup.ty);
+  let supported = match (Version::from(version), Version::from(minimum)) {
+    (Some(version), Some(minimum)) => version >= minimum,
+    _ => false,
+  };
+  if !supported {
+    return Some(format!(
+      "{}（版本 {}）不支持模块，至少需要版本 {}。",
+      setup.name, version, minimum
+    ));
+  }
+  None
+}
+
+/// 通过 -print-file-name 查找标准库附带的 modules.json，并读取其中 std 模块的源文件
+fn std_module_from_json(compiler: &Path, extra: &[&str], json_name: &str) -> Option<PathBuf> {
+  let mut command = Command::new(compiler);
+  #[cfg(windows)]
+  command.creation_flags(CREATE_NO_WINDOW);
+  let output = command
+    .args(extra)
+    .arg(format!("-print-file-name={}", json_name))
+    .stdin(Stdio::null())
+    .output()
+    .ok()?;
+  // 找不到时原样输出文件名
+  let json_path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+  if !json_path.is_absolute() {
+    debug!("编译器未提供 {}。", json_name);
+    return None;
+  }
+  let json: serde_json::Value =
+    serde_json::from_str(&std::fs::read_to_string(&json_path).ok()?).ok()?;
+  let source = json["modules"]
+    .as_array()?
+    .iter()
+    .find(|m| m["logical-name"] == "std")?["source-path"]
+    .as_str()?;
+  let path = json_path.parent()?.join(source);
+  path.exists().then_some(path)
+}
+
+/// 标准库 std 模块的源文件，以及编译时需要追加的选项。不支持 import std 时返回 None
+pub fn std_module(setup: &CompilerSetup, compiler: &Path) -> Option<(PathBuf, Vec<String>)> {
+  let result = match setup.ty {
+    CompilerType::GCC => {
+      std_module_from_json(compiler, &[], "libstdc++.modules.json").map(|p| (p, vec![]))
+    }
+    // Clang 的 std 模块由 libc++ 提供
+    CompilerType::LLVM => {
+      std_module_from_json(compiler, &["-stdlib=libc++"], "libc++.modules.json")
+        .map(|p| (p, vec!["-stdlib=libc++".to_string()]))
+    }
+    // ...\VC\Tools\MSVC\<版本>\bin\Hostx64\x64\cl.exe => ...\VC\Tools\MSVC\<版本>\modules\std.ixx
+    CompilerType::MSVC => compiler
+      .ancestors()
+      .nth(4)
+      .map(|p| p.join("modules").join("std.ixx"))
+      .filter(|p| p.exists())
+      .map(|p| (p, vec![])),
+  };
+  match &result {
+    Some((path, _)) => info!("std 模块的源文件：{}", path.to_string()),
+    None => info!("编译器未提供 std 模块，无法使用 import std。"),
+  }
+  result
+}
+
+/// 不支持模块时报告错误，使配置在写入文件前中止
+pub fn check(args: &TaskArgs) -> Result<()> {
+  match &args.module_error {
+    Some(e) => Err(anyhow!("{}", e)),
+    None => Ok(()),
+  }
+}
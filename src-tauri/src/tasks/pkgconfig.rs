@@ -0,0 +1,246 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::process::{Command, Stdio};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+
+use super::TaskArgs;
+#[cfg(windows)]
+use crate::utils::winapi::CREATE_NO_WINDOW;
+
+/// pkg-config 程序。可用 PKG_CONFIG 环境变量指定，如 pkgconf
+fn program() -> String {
+  std::env::var("PKG_CONFIG").unwrap_or_else(|_| "pkg-config".to_string())
+}
+
+fn install_hint(name: &str) -> String {
+  if cfg!(windows) {
+    format!(
+      "若使用 MSYS2，可运行 pacman -S mingw-w64-x86_64-{}，并将 MSYS2 的 mingw64/bin 加入 PATH。",
+      name
+    )
+  } else if cfg!(target_os = "macos") {
+    format!("可运行 brew install {}。", name)
+  } else {
+    format!(
+      "请使用包管理器安装其开发包，如 apt install lib{}-dev 或 dnf install {}-devel。",
+      name, name
+    )
+  }
+}
+
+/// 按空白拆分 pkg-config 的输出。路径中的空格以反斜杠转义，也可能以引号包含
+fn split(text: &str) -> Vec<String> {
+  let mut result = vec![];
+  let mut current = String::new();
+  let mut in_word = false;
+  let mut quote = None;
+  let mut chars = text.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' if quote != Some('\'') && !cfg!(windows) => {
+        if let Some(next) = chars.next() {
+          current.push(next);
+        }
+        in_word = true;
+      }
+      '"' | '\'' if quote.is_none() => {
+        quote = Some(c);
+        in_word = true;
+      }
+      c if Some(c) == quote => quote = None,
+      c if c.is_whitespace() && quote.is_none() => {
+        if in_word {
+          result.push(std::mem::take(&mut current));
+          in_word = false;
+        }
+      }
+      c => {
+        current.push(c);
+        in_word = true;
+      }
+    }
+  }
+  if in_word {
+    result.push(current);
+  }
+  result
+}
+
+/// 带有单独参数的选项，与其参数作为一组处理
+static WITH_ARGUMENT: &[&str] = &[
+  "-framework",
+  "-include",
+  "-imacros",
+  "-isystem",
+  "-idirafter",
+  "-Xlinker",
+  "-I",
+  "-L",
+  "-D",
+  "-U",
+];
+
+/// 重复出现时只保留第一个的选项组：头文件与库路径、宏定义。链接库的顺序有意义，重复的 -l 均保留
+static DEDUPLICATED: &[&str] = &[
+  "-I",
+  "-L",
+  "-D",
+  "-U",
+  "-isystem",
+  "-idirafter",
+  "/I",
+  "/D",
+  "/LIBPATH:",
+];
+
+fn is_deduplicated(group: &[String]) -> bool {
+  DEDUPLICATED.iter().any(|p| group[0].starts_with(p))
+}
+
+/// 将一个库的选项追加到已有选项之后
+fn merge(flags: &mut Vec<String>, output: &str) {
+  let tokens = split(output);
+  let mut groups: Vec<Vec<String>> = vec![];
+  let mut i = 0;
+  while i < tokens.len() {
+    let size = if WITH_ARGUMENT.contains(&tokens[i].as_str()) && i + 1 < tokens.len() {
+      2
+    } else {
+      1
+    };
+    groups.push(tokens[i..i + size].to_vec());
+    i += size;
+  }
+  for group in groups {
+    if is_deduplicated(&group) && flags.windows(group.len()).any(|w| w == group.as_slice()) {
+      continue;
+    }
+    flags.extend(group);
+  }
+}
+
+/// 通过 pkg-config 查询库的编译与链接选项。MSVC 使用 --msvc-syntax 输出
+pub fn resolve(libraries: &[String], msvc: bool) -> Result<Vec<String>> {
+  let mut flags: Vec<String> = vec![];
+  for name in libraries {
+    let mut command = Command::new(program());
+    #[cfg(windows)]
+    command.creation_flags(CREATE_NO_WINDOW);
+    if msvc {
+      command.arg("--msvc-syntax");
+    }
+    let output = command
+      .args(["--cflags", "--libs", name])
+      .stdin(Stdio::null())
+      .output()
+      .map_err(|_| {
+        anyhow!(
+          "找不到 pkg-config，无法查询库 {}。{}",
+          name,
+          install_hint("pkg-config")
+        )
+      })?;
+    if !output.status.success() {
+      debug!("pkg-config: {}", String::from_utf8_lossy(&output.stderr));
+      return Err(anyhow!(
+        "pkg-config 找不到库 {}。{}若已安装，请检查 PKG_CONFIG_PATH 环境变量。",
+        name,
+        install_hint(name)
+      ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    info!("库 {} 的编译选项：{}", name, stdout.trim());
+    merge(&mut flags, &stdout);
+  }
+  Ok(flags)
+}
+
+/// 头文件搜索路径，供 IntelliSense 使用
+pub fn include_dirs(args: &TaskArgs) -> Vec<String> {
+  let prefix = if args.setup.is_msvc() { "/I" } else { "-I" };
+  args
+    .pkg_flags
+    .iter()
+    .filter_map(|f| f.strip_prefix(prefix))
+    .filter(|d| !d.is_empty())
+    .map(|d| d.to_string())
+    .collect()
+}
+
+/// 查询失败时报告错误，使配置在写入文件前中止
+pub fn check(args: &TaskArgs) -> Result<()> {
+  match &args.pkg_error {
+    Some(e) => Err(anyhow!("{}", e)),
+    None => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_split() {
+    assert_eq!(split(" -lm  -DA=1\n"), vec!["-lm", "-DA=1"]);
+    assert_eq!(
+      split(r#"-I"C:/a b" -DX='1 2'"#),
+      vec!["-IC:/a b", "-DX=1 2"]
+    );
+    if !cfg!(windows) {
+      assert_eq!(
+        split(r"-I/opt/my\ lib/include"),
+        vec!["-I/opt/my lib/include"]
+      );
+    }
+  }
+
+  #[test]
+  fn test_merge() {
+    let mut flags = vec![];
+    merge(&mut flags, "-I/usr/include/gtk -DGTK -lgtk -lglib");
+    merge(
+      &mut flags,
+      "-I/usr/include/gtk -DGTK -include config.h -framework Cocoa -framework Metal -lglib",
+    );
+    merge(&mut flags, "-include other.h -framework Cocoa");
+    assert_eq!(
+      flags,
+      vec![
+        "-I/usr/include/gtk",
+        "-DGTK",
+        "-lgtk",
+        "-lglib",
+        "-include",
+        "config.h",
+        "-framework",
+        "Cocoa",
+        "-framework",
+        "Metal",
+        "-lglib",
+        "-include",
+        "other.h",
+        "-framework",
+        "Cocoa",
+      ]
+    );
+  }
+}
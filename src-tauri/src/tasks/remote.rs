@@ -0,0 +1,125 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use super::dotvscode::{build_task_label, program_dir, program_path, EXT, PROJECT_PROGRAM};
+use super::TaskArgs;
+use crate::steps::options::{BuildMode, BuildVariant, RemoteDebug};
+
+pub static TASK_LABEL: &str = "start gdbserver";
+
+static SSH_TEMPLATE: &str =
+  "scp \"{program}\" {host}:{dir}/{name} && ssh {host} gdbserver :{port} {dir}/{name}";
+static DOCKER_TEMPLATE: &str =
+  "docker cp \"{program}\" {host}:{dir}/{name} && docker exec {host} gdbserver :{port} {dir}/{name}";
+
+fn template(remote: &RemoteDebug) -> &str {
+  match remote.command.as_str() {
+    "ssh" => SSH_TEMPLATE,
+    "docker" => DOCKER_TEMPLATE,
+    custom => custom,
+  }
+}
+
+/// 可执行文件的文件名，不含路径
+fn program_name(args: &TaskArgs) -> String {
+  match args.build_mode {
+    BuildMode::CMake => "${command:cmake.getLaunchTargetFilename}".to_string(),
+    BuildMode::Single => format!("${{fileBasenameNoExtension}}.{}", EXT),
+    _ => format!("{}.{}", PROJECT_PROGRAM, EXT),
+  }
+}
+
+/// GDB 连接 gdbserver 的地址。docker 容器须将端口映射到本机
+fn server_address(remote: &RemoteDebug) -> String {
+  let host = if remote.command == "docker" {
+    "localhost"
+  } else {
+    remote.host.rsplit('@').next().unwrap()
+  };
+  format!("{}:{}", host, remote.port)
+}
+
+fn check_supported(args: &TaskArgs) -> Result<()> {
+  if args.setup.is_msvc() {
+    return Err(anyhow!("MSVC 不支持通过 gdbserver 远程调试。"));
+  }
+  Ok(())
+}
+
+/// 复制程序并启动 gdbserver 的后台任务。gdbserver 开始监听后即视为就绪，调试器随后连接
+pub fn gdbserver_task(args: &TaskArgs, remote: &RemoteDebug) -> Result<serde_json::Value> {
+  check_supported(args)?;
+  let command = template(remote)
+    .replace("{program}", &program_path(args, BuildVariant::Debug))
+    .replace("{name}", &program_name(args))
+    .replace("{host}", &remote.host)
+    .replace("{port}", &remote.port.to_string())
+    .replace("{dir}", &remote.dir);
+  Ok(json!({
+    "type": "shell",
+    "label": TASK_LABEL,
+    "command": command,
+    "dependsOn": build_task_label(args, BuildVariant::Debug),
+    "isBackground": true,
+    "presentation": {
+      "reveal": "always",
+      "focus": false,
+      "echo": true,
+      "showReuseMessage": false,
+      "panel": "dedicated",
+      "clear": true
+    },
+    "problemMatcher": {
+      "owner": "gdbserver",
+      "pattern": {
+        "regexp": "^__vscch_never_matches__$"
+      },
+      "background": {
+        "activeBegin": true,
+        "beginsPattern": "^Process .* created",
+        "endsPattern": "^Listening on port"
+      }
+    }
+  }))
+}
+
+/// 连接 gdbserver 的调试配置。GDB 在本机读取程序的调试信息
+pub fn launch_config(
+  args: &TaskArgs,
+  remote: &RemoteDebug,
+  mi_settings: serde_json::Map<String, serde_json::Value>,
+) -> Result<serde_json::Value> {
+  check_supported(args)?;
+  let mut config = json!({
+    "name": "remote debug",
+    "type": "cppdbg",
+    "request": "launch",
+    "program": program_path(args, BuildVariant::Debug),
+    "cwd": program_dir(args),
+    "miDebuggerServerAddress": server_address(remote),
+    // 远程机器上程序所在的文件夹对应本机的输出文件夹
+    "sourceFileMap": {
+      &remote.dir: program_dir(args)
+    },
+    "preLaunchTask": TASK_LABEL
+  });
+  config.as_object_mut().unwrap().extend(mi_settings);
+  Ok(config)
+}
@@ -16,13 +16,13 @@
 // along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
-use log::warn;
+use log::{info, warn};
 use serde_json::json;
 
-use super::TaskArgs;
+use super::{write_file, Content, TaskArgs};
 
 pub fn create_checker(_: &TaskArgs) -> Result<()> {
   let path = dirs::data_dir()
@@ -42,7 +42,73 @@ pub fn checker_path() -> Result<PathBuf> {
   )
 }
 
-pub fn create_keybinding(args: &TaskArgs) -> Result<()> {
+/// 是否位于 AppImage 运行时的临时挂载点（/tmp/.mount_XXXXXX）中
+fn in_appimage_mount(path: &Path) -> bool {
+  path
+    .components()
+    .any(|c| c.as_os_str().to_string_lossy().starts_with(".mount_"))
+}
+
+/// 可执行文件所在的位置是否可能在之后被清理，如临时文件夹与下载文件夹
+fn is_unstable(path: &Path) -> bool {
+  path.starts_with(std::env::temp_dir())
+    || dirs::download_dir().map_or(false, |d| path.starts_with(d))
+}
+
+/// 复制到数据文件夹中的 vscch
+fn installed_path() -> Result<PathBuf> {
+  Ok(
+    dirs::data_dir()
+      .ok_or(anyhow!("找不到用于存放 vscch 的路径。"))?
+      .join("vscch")
+      .join(if cfg!(windows) { "vscch.exe" } else { "vscch" }),
+  )
+}
+
+/// vscch 自身的路径，供生成的任务调用其子命令。AppImage 使用镜像文件本身；
+/// 位于临时文件夹或下载文件夹中时，使用复制到数据文件夹中的副本
+pub fn self_path() -> Result<PathBuf> {
+  if let Some(appimage) = std::env::var_os("APPIMAGE") {
+    return Ok(appimage.into());
+  }
+  let exe = std::env::current_exe()?;
+  if in_appimage_mount(&exe) {
+    return Err(anyhow!(
+      "vscch 位于 AppImage 的临时挂载点 {:?} 中，退出后即失效，但未设置 APPIMAGE 环境变量。请直接运行 AppImage 文件。",
+      exe
+    ));
+  }
+  if is_unstable(&exe) {
+    installed_path()
+  } else {
+    Ok(exe)
+  }
+}
+
+/// 将位于临时位置的 vscch 复制到数据文件夹，使生成的任务在其被清理后仍可使用
+pub fn install_self(_: &TaskArgs) -> Result<()> {
+  let exe = std::env::current_exe()?;
+  let path = self_path()?;
+  if path == exe || std::env::var_os("APPIMAGE").is_some() {
+    return Ok(());
+  }
+  info!(
+    "vscch 位于临时位置 {:?}，将复制到 {:?} 供生成的任务使用。",
+    exe, path
+  );
+  fs::create_dir_all(path.parent().unwrap())?;
+  fs::copy(&exe, &path).map_err(|e| {
+    anyhow!(
+      "无法将 vscch 复制到 {:?}：{}。请关闭正在运行的任务后重试，或将 vscch 移到固定位置再运行。",
+      path,
+      e
+    )
+  })?;
+  Ok(())
+}
+
+/// 返回 keybindings.json 的路径，以及合并了运行快捷键后的内容
+pub fn keybinding_content(args: &TaskArgs) -> Result<(PathBuf, serde_json::Value)> {
   let key = &args.run_hotkey;
   let command = "workbench.action.tasks.runTask";
   let args = "run and pause";
@@ -52,8 +118,13 @@ pub fn create_keybinding(args: &TaskArgs) -> Result<()> {
     .join("Code")
     .join("User")
     .join("keybindings.json");
-  fs::create_dir_all(filepath.parent().unwrap())?;
+  let binding = json!({
+    "key": key,
+    "command": command,
+    "args": args
+  });
   let mut result = vec![];
+  let mut replaced = false;
   if filepath.exists() {
     let content = fs::read_to_string(&filepath)?;
     // JSON5 是 vscode JSON with comment 的超集。直接用 serde_json 可能出错
@@ -62,20 +133,41 @@ pub fn create_keybinding(args: &TaskArgs) -> Result<()> {
       let this_key = i["key"].as_str().ok_or(anyhow!(
         "keybindings.json 中的 \"key\" 字段应为 string 类型。"
       ))?;
-      if this_key == key {
-        // Warning for overwriting
-        warn!("快捷键 {} 已有配置，将被覆盖。", key);
-      } else {
+      if this_key != key {
         result.push(i.clone());
+      } else if !replaced {
+        // 原地替换，使重复配置时文件内容保持不变
+        if i != &binding {
+          warn!("快捷键 {} 已有配置，将被覆盖。", key);
+        }
+        result.push(binding.clone());
+        replaced = true;
       }
     }
   }
-  result.push(json!({
-    "key": key,
-    "command": command,
-    "args": args
-  }));
-  let result = serde_json::Value::Array(result);
-  fs::write(filepath, serde_json::to_string_pretty(&result)?)?;
-  Ok(())
+  if !replaced {
+    result.push(binding);
+  }
+  Ok((filepath, serde_json::Value::Array(result)))
+}
+
+pub fn create_keybinding(args: &TaskArgs) -> Result<()> {
+  let (filepath, json) = keybinding_content(args)?;
+  fs::create_dir_all(filepath.parent().unwrap())?;
+  write_file(args, filepath, Content::Json(json))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_unstable_path() {
+    assert!(in_appimage_mount(Path::new(
+      "/tmp/.mount_vscchAbC/usr/bin/vscch"
+    )));
+    assert!(!in_appimage_mount(Path::new("/usr/bin/vscch")));
+    assert!(is_unstable(&std::env::temp_dir().join("vscch")));
+    assert!(!is_unstable(&installed_path().unwrap()));
+  }
 }
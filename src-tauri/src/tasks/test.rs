@@ -53,7 +53,6 @@ int main() {
 #[cfg(target_os = "macos")]
 mod key {
   pub static CTRL_CMD: &str = "⌘";
-  pub static CTRL: &str = "⌃";
   pub static SHIFT: &str = "⇧";
   pub static SEP: &str = " ";
 }
@@ -61,7 +60,6 @@ mod key {
 #[cfg(not(target_os = "macos"))]
 mod key {
   pub static CTRL_CMD: &str = "Ctrl";
-  pub static CTRL: &str = "Ctrl";
   pub static SHIFT: &str = "Shift";
   pub static SEP: &str = " + ";
 }
@@ -80,11 +78,7 @@ pub fn generate(args: &TaskArgs) -> Result<()> {
 
   let helloworld = if args.is_c { &C_HELLOWORLD } else { &CPP_HELLOWORLD };
 
-  let run_key = if args.compatible_mode {
-    format!("{}{}F5", CTRL, SEP)
-  } else {
-    "F6".to_string()
-  };
+  let run_key = args.run_hotkey.to_uppercase();
 
   fs::write(
     test_file,
@@ -18,6 +18,19 @@
 pub mod winreg;
 pub mod winapi;
 pub mod sysctl;
+pub mod terminal;
+
+/// 标准输出能否显示 ANSI 颜色：须为终端，Windows 上还须控制台支持虚拟终端序列
+pub fn ansi_enabled() -> bool {
+  use std::io::IsTerminal;
+  if !std::io::stdout().is_terminal() {
+    return false;
+  }
+  #[cfg(windows)]
+  return winapi::enable_virtual_terminal();
+  #[cfg(not(windows))]
+  return true;
+}
 
 pub trait ToString { 
   fn to_string(&self) -> String;
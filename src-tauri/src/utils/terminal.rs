@@ -0,0 +1,59 @@
+// Copyright (C) 2022 Guyutongxue
+//
+// This file is part of vscch4.
+//
+// vscch4 is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// vscch4 is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with vscch4.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+/// 终端模拟器，以及在其中执行命令时置于命令之前的参数
+pub struct Terminal {
+  pub path: PathBuf,
+  pub exec_args: &'static [&'static str],
+}
+
+static KNOWN_TERMINALS: &[(&str, &[&str])] = &[
+  // Debian 系发行版上由 update-alternatives 注册的默认终端
+  ("x-terminal-emulator", &["-e"]),
+  ("gnome-terminal", &["--"]),
+  ("konsole", &["-e"]),
+  ("kitty", &[]),
+  ("alacritty", &["-e"]),
+  ("wezterm", &["start", "--"]),
+  ("xterm", &["-e"]),
+];
+
+/// 查找可用的终端模拟器。优先使用 $TERMINAL，其次按常见程度查找
+pub fn detect() -> Option<Terminal> {
+  if let Some(terminal) = std::env::var_os("TERMINAL").filter(|t| !t.is_empty()) {
+    if let Ok(path) = which::which(&terminal) {
+      let name = path.file_name().unwrap().to_string_lossy().to_string();
+      // 已知的终端使用其自己的参数；其它终端按惯例使用 -e
+      let exec_args = KNOWN_TERMINALS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map_or(&["-e"] as &[&str], |(_, a)| a);
+      return Some(Terminal {
+        path: path,
+        exec_args: exec_args,
+      });
+    }
+  }
+  KNOWN_TERMINALS.iter().find_map(|(name, exec_args)| {
+    which::which(name).ok().map(|path| Terminal {
+      path: path,
+      exec_args: exec_args,
+    })
+  })
+}
@@ -36,6 +36,7 @@ use windows::Win32::System::Environment::ExpandEnvironmentStringsW;
 use windows::Win32::UI::Shell::{IShellLinkW, SHGetKnownFolderPath, ShellLink, KNOWN_FOLDER_FLAG};
 
 pub static CREATE_NO_WINDOW: u32 = windows::Win32::System::Threading::CREATE_NO_WINDOW.0;
+pub static CREATE_NEW_CONSOLE: u32 = windows::Win32::System::Threading::CREATE_NEW_CONSOLE.0;
 
 fn to_vec(s: &str) -> Vec<u16> {
   s.encode_utf16().chain(std::iter::once(0)).collect()
@@ -126,6 +127,10 @@ pub fn alloc_console() -> bool {
   unsafe { Console::FreeConsole().as_bool() && Console::AllocConsole().as_bool() }
 }
 
+pub fn attach_console() -> bool {
+  unsafe { Console::AttachConsole(Console::ATTACH_PARENT_PROCESS).as_bool() }
+}
+
 pub fn enable_virtual_terminal() -> bool {
   unsafe {
     let handle = Console::GetStdHandle(Console::STD_OUTPUT_HANDLE).unwrap();
@@ -23,11 +23,45 @@ export type Compiler = {
   version: string;
   packageString: string;
 };
+export type RemoteDebug = {
+  host: string;
+  port: number;
+  dir: string;
+  command: string;
+};
 export type OptionsBase = {
   runHotkey: string;
-  compatibleMode: boolean;
+  runLocation: string;
   activeLanguage: string;
   activeStandard: string | null;
+  warningLevel: string;
+  pedantic: boolean;
+  useGnu: boolean;
+  acpOutput: boolean;
+  diagnosticsColor: string;
+  includeDirs: string[];
+  libDirs: string[];
+  libs: string[];
+  defines: string[];
+  modules: boolean;
+  pch: string | null;
+  buildMode: string;
+  runVariant: string;
+  outputDir: string;
+  exclude: string[];
+  directives: boolean;
+  autoLibs: boolean;
+  libRules: string[];
+  libraries: string[];
+  intellisense: string;
+  workspaceSettings: boolean;
+  ioFiles: boolean;
+  gitignore: boolean;
+  debugger: string;
+  gdbPrettyPrinters: boolean;
+  gdbSkipStd: boolean;
+  gdbPrintObject: boolean;
+  remote: RemoteDebug | null;
   asciiCheck: boolean;
   removeExtensions: boolean;
   addToPath: boolean;
@@ -38,12 +72,7 @@ export type OptionsBase = {
 };
 
 export type OptionsProfile = OptionsBase & {
-  useGnu: boolean;
-  pedantic: boolean;
-  activeWarning: string;
   activeOptLevel: string;
-  werror: boolean;
-  acpOutput: boolean;
   staticStd: boolean;
   customArgs: string[];
 }
@@ -54,9 +83,37 @@ export type Options = OptionsBase & {
 
 export const DEFAULT_PROFILE: OptionsProfile = {
   runHotkey: "f6",
-  compatibleMode: false,
+  runLocation: "external",
   activeLanguage: "C++",
   activeStandard: null,
+  warningLevel: "default",
+  pedantic: false,
+  useGnu: false,
+  acpOutput: false,
+  diagnosticsColor: "auto",
+  includeDirs: [],
+  libDirs: [],
+  libs: [],
+  defines: [],
+  modules: false,
+  pch: null,
+  buildMode: "single",
+  runVariant: "debug",
+  outputDir: "${workspaceFolder}/build",
+  exclude: [],
+  directives: false,
+  autoLibs: false,
+  libRules: [],
+  libraries: [],
+  intellisense: "cpptools",
+  workspaceSettings: true,
+  ioFiles: false,
+  gitignore: false,
+  debugger: "codelldb",
+  gdbPrettyPrinters: true,
+  gdbSkipStd: true,
+  gdbPrintObject: true,
+  remote: null,
   asciiCheck: false,
   removeExtensions: false,
   addToPath: true,
@@ -64,21 +121,44 @@ export const DEFAULT_PROFILE: OptionsProfile = {
   test: null,
   desktopShortcut: false,
   collectData: true,
-  useGnu: false,
-  pedantic: false,
-  activeWarning: "default",
   activeOptLevel: "default",
-  werror: false,
-  acpOutput: false,
   staticStd: false,
   customArgs: []
 };
 
 export const NEWBIE_PROFILE: OptionsProfile = {
   runHotkey: "f6",
-  compatibleMode: false,
+  runLocation: "external",
   activeLanguage: "C++",
   activeStandard: null,
+  warningLevel: "strict",
+  pedantic: true,
+  useGnu: false,
+  acpOutput: true,
+  diagnosticsColor: "auto",
+  includeDirs: [],
+  libDirs: [],
+  libs: [],
+  defines: [],
+  modules: false,
+  pch: null,
+  buildMode: "single",
+  runVariant: "debug",
+  outputDir: "${workspaceFolder}/build",
+  exclude: [],
+  directives: false,
+  autoLibs: false,
+  libRules: [],
+  libraries: [],
+  intellisense: "cpptools",
+  workspaceSettings: true,
+  ioFiles: false,
+  gitignore: false,
+  debugger: "codelldb",
+  gdbPrettyPrinters: true,
+  gdbSkipStd: true,
+  gdbPrintObject: true,
+  remote: null,
   asciiCheck: true,
   removeExtensions: true,
   addToPath: true,
@@ -86,16 +166,32 @@ export const NEWBIE_PROFILE: OptionsProfile = {
   desktopShortcut: true,
   test: null,
   collectData: true,
-  useGnu: false,
-  pedantic: true,
-  activeWarning: "extra",
   activeOptLevel: "default",
-  werror: true,
-  acpOutput: true,
   staticStd: false,
   customArgs: []
 };
 
+/** 将旧版本保存的配置转换为当前的格式，缺少的选项取默认值 */
+export function migrateProfile(saved: any): OptionsProfile {
+  const { compatibleMode, activeWarning, werror, ...rest } = saved;
+  const profile: OptionsProfile = { ...DEFAULT_PROFILE, ...rest };
+  // 兼容模式即在集成终端中运行
+  if (typeof compatibleMode === "boolean" && !("runLocation" in saved)) {
+    profile.runLocation = compatibleMode ? "integrated" : "external";
+  }
+  // 旧的警告选项（default、all、extra）与“视警告为错误”合并为警告等级
+  if (typeof activeWarning === "string" && !("warningLevel" in saved)) {
+    if (werror) {
+      profile.warningLevel = "strict";
+    } else if (activeWarning === "default") {
+      profile.warningLevel = "default";
+    } else {
+      profile.warningLevel = "extra";
+    }
+  }
+  return profile;
+}
+
 export const vscode = writable<string | null>(null);
 export const compiler = writable<Compiler | null>(null);
 export const workspace = writable<string | null>(null);